@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::parse;
+use crate::parse::ParseError;
+
+// Whatever a part of a puzzle comes out as.
+#[derive(Clone, Debug)]
+pub enum Answer {
+    Unsigned(u128),
+    Signed(i128),
+    Text(String),
+    NotImplemented,
+}
+
+impl Answer {
+    // Both integer kinds as one, if it fits.
+    fn as_i128(&self) -> Option<i128> {
+        return match self {
+            Answer::Unsigned(n) => i128::try_from(*n).ok(),
+            Answer::Signed(n) => Some(*n),
+            _ => None,
+        };
+    }
+}
+
+// Numbers compare by value, so a solver can pick either integer kind and
+// still match what's parsed from a file.
+impl PartialEq for Answer {
+    fn eq(&self, other: &Self) -> bool {
+        return match (self, other) {
+            (Answer::Unsigned(a), Answer::Unsigned(b)) => a == b,
+            (Answer::Text(a), Answer::Text(b)) => a == b,
+            (Answer::NotImplemented, Answer::NotImplemented) => true,
+            (a, b) => a.as_i128().is_some() && a.as_i128() == b.as_i128(),
+        };
+    }
+}
+
+impl Eq for Answer {}
+
+impl Display for Answer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            Answer::Unsigned(n) => write!(f, "{}", n),
+            Answer::Signed(n) => write!(f, "{}", n),
+            Answer::Text(s) => write!(f, "{}", s),
+            Answer::NotImplemented => write!(f, "not implemented"),
+        };
+    }
+}
+
+// The inverse of `Display`: numbers if they look like numbers, otherwise text.
+impl FromStr for Answer {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(ParseError::at_col(1, "Missing answer"));
+        }
+
+        if s == "not implemented" {
+            return Ok(Answer::NotImplemented);
+        }
+        if let Ok(n) = s.parse() {
+            return Ok(Answer::Unsigned(n));
+        }
+        if let Ok(n) = s.parse() {
+            return Ok(Answer::Signed(n));
+        }
+        return Ok(Answer::Text(s.to_string()));
+    }
+}
+
+impl From<u128> for Answer {
+    fn from(n: u128) -> Self {
+        return Answer::Unsigned(n);
+    }
+}
+
+impl From<usize> for Answer {
+    fn from(n: usize) -> Self {
+        return Answer::Unsigned(n as u128);
+    }
+}
+
+impl From<i128> for Answer {
+    fn from(n: i128) -> Self {
+        return Answer::Signed(n);
+    }
+}
+
+impl From<String> for Answer {
+    fn from(s: String) -> Self {
+        return Answer::Text(s);
+    }
+}
+
+impl From<&str> for Answer {
+    fn from(s: &str) -> Self {
+        return Answer::Text(s.to_string());
+    }
+}
+
+// One `<day> <part> <answer>` per line, the answer being the rest of the line.
+// Blank lines and `#` comments are skipped.
+pub fn parse_expected(s: &str) -> Result<HashMap<(u8, u8), Answer>, ParseError> {
+    let mut out = HashMap::new();
+    for (idx, l) in s.lines().enumerate() {
+        let on_line = |e: ParseError| e.on_line(idx + 1);
+        if l.trim().is_empty() || l.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let mut toks = parse::tokens(l);
+        let mut number = |reason| {
+            let (col, tok) = toks.next().ok_or(ParseError::at_col(l.len() + 1, reason))?;
+            return tok
+                .parse::<u8>()
+                .map_err(|_| ParseError::at_col(col, reason));
+        };
+        let day = number("Not a day").map_err(on_line)?;
+        let part = number("Not a part").map_err(on_line)?;
+
+        let Some((col, _)) = toks.next() else {
+            return Err(on_line(ParseError::at_col(l.len() + 1, "Missing answer")));
+        };
+        let answer = l[col - 1..].trim_end().parse().map_err(on_line)?;
+        if out.insert((day, part), answer).is_some() {
+            return Err(on_line(ParseError::at_col(col, "Duplicate answer")));
+        }
+    }
+
+    return Ok(out);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::answer::parse_expected;
+    use crate::answer::Answer;
+    use crate::parse::ParseError;
+
+    #[test]
+    fn display_round_trips() {
+        for a in [
+            Answer::Unsigned(u128::MAX),
+            Answer::Signed(-12),
+            Answer::Text("6,0,3,1".to_string()),
+            Answer::NotImplemented,
+        ] {
+            assert_eq!(a.to_string().parse::<Answer>(), Ok(a));
+        }
+    }
+
+    #[test]
+    fn integers_compare_by_value() {
+        assert_eq!(Answer::Signed(5), Answer::Unsigned(5));
+        assert_eq!(Answer::Unsigned(5), Answer::from(5usize));
+        assert_ne!(Answer::Signed(-1), Answer::Unsigned(u128::MAX));
+        assert_ne!(Answer::Unsigned(u128::MAX), Answer::Signed(-1));
+        assert_ne!(Answer::Text("5".to_string()), Answer::Unsigned(5));
+        assert_ne!(Answer::NotImplemented, Answer::from("not"));
+    }
+
+    #[test]
+    fn expected_file() {
+        let expected = parse_expected(
+            "# day part answer\n\
+             1 1 936063\n\
+             \n\
+             1 2 -7\n\
+             6 2 not implemented\n\
+             7 1 12, 34  \n",
+        )
+        .unwrap();
+        assert_eq!(expected.len(), 4);
+        assert_eq!(expected[&(1, 1)], Answer::Unsigned(936063));
+        assert_eq!(expected[&(1, 2)], Answer::Signed(-7));
+        assert_eq!(expected[&(6, 2)], Answer::NotImplemented);
+        assert_eq!(expected[&(7, 1)], Answer::from("12, 34"));
+    }
+
+    #[test]
+    fn expected_file_errors() {
+        assert_eq!(
+            parse_expected("1 1 5\nx 1 5").unwrap_err(),
+            ParseError {
+                line: 2,
+                col: 1,
+                reason: "Not a day",
+            }
+        );
+        assert_eq!(
+            parse_expected("1").unwrap_err(),
+            ParseError::at_col(2, "Not a part")
+        );
+        assert_eq!(
+            parse_expected("1 2  ").unwrap_err(),
+            ParseError::at_col(6, "Missing answer")
+        );
+        assert_eq!(
+            parse_expected("1 2 3\n1 2 4").unwrap_err(),
+            ParseError {
+                line: 2,
+                col: 5,
+                reason: "Duplicate answer",
+            }
+        );
+    }
+}
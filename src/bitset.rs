@@ -0,0 +1,429 @@
+use crate::grid::Dir4;
+use crate::grid::Grid;
+use crate::grid::Point;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+// Flat bit storage shared by both sets; they only differ in how a point maps to a bit.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Bits(Vec<u64>);
+
+impl Bits {
+    fn new(len: usize) -> Bits {
+        return Bits(vec![0; len.div_ceil(WORD_BITS)]);
+    }
+
+    fn insert(&mut self, idx: usize) -> bool {
+        let mask = 1 << (idx % WORD_BITS);
+        let word = &mut self.0[idx / WORD_BITS];
+        let fresh = *word & mask == 0;
+        *word |= mask;
+        return fresh;
+    }
+
+    fn remove(&mut self, idx: usize) -> bool {
+        let mask = 1 << (idx % WORD_BITS);
+        let word = &mut self.0[idx / WORD_BITS];
+        let present = *word & mask != 0;
+        *word &= !mask;
+        return present;
+    }
+
+    fn contains(&self, idx: usize) -> bool {
+        return self.0[idx / WORD_BITS] & (1 << (idx % WORD_BITS)) != 0;
+    }
+
+    fn count(&self) -> usize {
+        return self.0.iter().map(|w| w.count_ones() as usize).sum();
+    }
+
+    fn clear(&mut self) {
+        self.0.fill(0);
+    }
+
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        return self.0.iter().enumerate().flat_map(|(w_idx, word)| {
+            let mut rest = *word;
+            return std::iter::from_fn(move || {
+                if rest == 0 {
+                    return None;
+                }
+                let bit = rest.trailing_zeros() as usize;
+                rest &= rest - 1;
+                return Some(w_idx * WORD_BITS + bit);
+            });
+        });
+    }
+
+    fn zip_with(&mut self, other: &Bits, f: impl Fn(u64, u64) -> u64) {
+        assert_eq!(self.0.len(), other.0.len(), "Sets are different sizes");
+        for (mine, theirs) in self.0.iter_mut().zip(other.0.iter()) {
+            *mine = f(*mine, *theirs);
+        }
+    }
+
+    fn is_subset(&self, other: &Bits) -> bool {
+        assert_eq!(self.0.len(), other.0.len(), "Sets are different sizes");
+        return self.0.iter().zip(other.0.iter()).all(|(a, b)| a & !b == 0);
+    }
+}
+
+// A set of points within a grid's bounds.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GridBitSet {
+    width: usize,
+    height: usize,
+    bits: Bits,
+}
+
+fn cell_index(width: usize, height: usize, p: &Point) -> Option<usize> {
+    if p.x() < 0 || p.y() < 0 {
+        return None;
+    }
+
+    let (x, y) = (p.x() as usize, p.y() as usize);
+    if x >= width || y >= height {
+        return None;
+    }
+
+    return Some(y * width + x);
+}
+
+fn cell_point(width: usize, idx: usize) -> Point {
+    return Point::new((idx % width) as isize, (idx / width) as isize);
+}
+
+impl GridBitSet {
+    pub fn new<T>(g: &Grid<T>) -> GridBitSet {
+        return GridBitSet::with_size(g.width(), g.height());
+    }
+
+    pub fn with_size(width: usize, height: usize) -> GridBitSet {
+        return GridBitSet {
+            width,
+            height,
+            bits: Bits::new(width * height),
+        };
+    }
+
+    fn index(&self, p: &Point) -> usize {
+        return cell_index(self.width, self.height, p).expect("Point outside of grid");
+    }
+
+    // Returns whether the point was newly added.
+    pub fn insert(&mut self, p: &Point) -> bool {
+        let idx = self.index(p);
+        return self.bits.insert(idx);
+    }
+
+    // Returns whether the point was present.
+    pub fn remove(&mut self, p: &Point) -> bool {
+        return match cell_index(self.width, self.height, p) {
+            Some(idx) => self.bits.remove(idx),
+            None => false,
+        };
+    }
+
+    pub fn contains(&self, p: &Point) -> bool {
+        return cell_index(self.width, self.height, p).is_some_and(|idx| self.bits.contains(idx));
+    }
+
+    pub fn count(&self) -> usize {
+        return self.bits.count();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.count() == 0;
+    }
+
+    pub fn clear(&mut self) {
+        self.bits.clear();
+    }
+
+    // Row by row, left to right.
+    pub fn iter(&self) -> impl Iterator<Item = Point> + '_ {
+        return self.bits.iter().map(|idx| cell_point(self.width, idx));
+    }
+
+    // Equal word counts aren't enough, the same bit means a different cell
+    // in a differently shaped grid.
+    fn assert_same_size(&self, other: &GridBitSet) {
+        assert_eq!(
+            (self.width, self.height),
+            (other.width, other.height),
+            "Sets are for different grid sizes"
+        );
+    }
+
+    pub fn union_with(&mut self, other: &GridBitSet) {
+        self.assert_same_size(other);
+        self.bits.zip_with(&other.bits, |a, b| a | b);
+    }
+
+    pub fn intersect_with(&mut self, other: &GridBitSet) {
+        self.assert_same_size(other);
+        self.bits.zip_with(&other.bits, |a, b| a & b);
+    }
+
+    pub fn difference_with(&mut self, other: &GridBitSet) {
+        self.assert_same_size(other);
+        self.bits.zip_with(&other.bits, |a, b| a & !b);
+    }
+
+    pub fn is_subset(&self, other: &GridBitSet) -> bool {
+        self.assert_same_size(other);
+        return self.bits.is_subset(&other.bits);
+    }
+}
+
+// A set of (point, heading) pairs, 4 bits per cell.
+// Handy for spotting a walk that revisits a cell facing the same way.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DirBitSet {
+    width: usize,
+    height: usize,
+    bits: Bits,
+}
+
+impl DirBitSet {
+    pub fn new<T>(g: &Grid<T>) -> DirBitSet {
+        return DirBitSet::with_size(g.width(), g.height());
+    }
+
+    pub fn with_size(width: usize, height: usize) -> DirBitSet {
+        return DirBitSet {
+            width,
+            height,
+            bits: Bits::new(width * height * Dir4::ALL.len()),
+        };
+    }
+
+    fn index(&self, p: &Point, d: Dir4) -> Option<usize> {
+        let cell = cell_index(self.width, self.height, p)?;
+        return Some(cell * Dir4::ALL.len() + d as usize);
+    }
+
+    // Returns whether the pair was newly added.
+    pub fn insert(&mut self, p: &Point, d: Dir4) -> bool {
+        let idx = self.index(p, d).expect("Point outside of grid");
+        return self.bits.insert(idx);
+    }
+
+    // Returns whether the pair was present.
+    pub fn remove(&mut self, p: &Point, d: Dir4) -> bool {
+        return match self.index(p, d) {
+            Some(idx) => self.bits.remove(idx),
+            None => false,
+        };
+    }
+
+    pub fn contains(&self, p: &Point, d: Dir4) -> bool {
+        return self.index(p, d).is_some_and(|idx| self.bits.contains(idx));
+    }
+
+    pub fn count(&self) -> usize {
+        return self.bits.count();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.count() == 0;
+    }
+
+    pub fn clear(&mut self) {
+        self.bits.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Point, Dir4)> + '_ {
+        return self.bits.iter().map(|idx| {
+            return (
+                cell_point(self.width, idx / Dir4::ALL.len()),
+                Dir4::ALL[idx % Dir4::ALL.len()],
+            );
+        });
+    }
+
+    // The cells visited in any direction.
+    pub fn cells(&self) -> GridBitSet {
+        let mut out = GridBitSet::with_size(self.width, self.height);
+        for idx in self.bits.iter() {
+            out.bits.insert(idx / Dir4::ALL.len());
+        }
+        return out;
+    }
+
+    fn assert_same_size(&self, other: &DirBitSet) {
+        assert_eq!(
+            (self.width, self.height),
+            (other.width, other.height),
+            "Sets are for different grid sizes"
+        );
+    }
+
+    pub fn union_with(&mut self, other: &DirBitSet) {
+        self.assert_same_size(other);
+        self.bits.zip_with(&other.bits, |a, b| a | b);
+    }
+
+    pub fn intersect_with(&mut self, other: &DirBitSet) {
+        self.assert_same_size(other);
+        self.bits.zip_with(&other.bits, |a, b| a & b);
+    }
+
+    pub fn difference_with(&mut self, other: &DirBitSet) {
+        self.assert_same_size(other);
+        self.bits.zip_with(&other.bits, |a, b| a & !b);
+    }
+
+    pub fn is_subset(&self, other: &DirBitSet) -> bool {
+        self.assert_same_size(other);
+        return self.bits.is_subset(&other.bits);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bitset::DirBitSet;
+    use crate::bitset::GridBitSet;
+    use crate::grid::Dir4;
+    use crate::grid::Grid;
+    use crate::grid::Point;
+
+    #[test]
+    fn sized_from_grid() {
+        let g: Grid<char> = "abc\ndef".parse().unwrap();
+        let mut s = GridBitSet::new(&g);
+        assert!(s.insert(&Point::new(2, 1)));
+        assert!(!s.contains(&Point::new(3, 1)));
+        assert!(!s.contains(&Point::new(0, 2)));
+        assert!(!s.contains(&Point::new(-1, 0)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Point outside of grid")]
+    fn insert_out_of_bounds() {
+        GridBitSet::with_size(3, 3).insert(&Point::new(3, 0));
+    }
+
+    #[test]
+    fn insert_contains_remove() {
+        let mut s = GridBitSet::with_size(100, 100);
+        assert!(s.is_empty());
+        assert!(s.insert(&Point::new(5, 7)));
+        assert!(!s.insert(&Point::new(5, 7)));
+        assert!(s.insert(&Point::new(99, 99)));
+        assert_eq!(s.count(), 2);
+        assert!(s.contains(&Point::new(5, 7)));
+        assert!(!s.contains(&Point::new(7, 5)));
+        assert!(s.remove(&Point::new(5, 7)));
+        assert!(!s.remove(&Point::new(5, 7)));
+        assert_eq!(s.count(), 1);
+        s.clear();
+        assert!(s.is_empty());
+    }
+
+    #[test]
+    fn iterates_in_reading_order() {
+        let mut s = GridBitSet::with_size(70, 3);
+        for p in [Point::new(69, 2), Point::new(1, 0), Point::new(0, 1)] {
+            s.insert(&p);
+        }
+        assert_eq!(
+            s.iter().collect::<Vec<_>>(),
+            vec![Point::new(1, 0), Point::new(0, 1), Point::new(69, 2)]
+        );
+    }
+
+    #[test]
+    fn set_operations() {
+        let mut a = GridBitSet::with_size(10, 10);
+        let mut b = GridBitSet::with_size(10, 10);
+        a.insert(&Point::new(1, 1));
+        a.insert(&Point::new(2, 2));
+        b.insert(&Point::new(2, 2));
+        b.insert(&Point::new(3, 3));
+
+        let mut union = a.clone();
+        union.union_with(&b);
+        assert_eq!(union.count(), 3);
+        assert!(a.is_subset(&union) && b.is_subset(&union));
+
+        let mut inter = a.clone();
+        inter.intersect_with(&b);
+        assert_eq!(inter.iter().collect::<Vec<_>>(), vec![Point::new(2, 2)]);
+
+        let mut diff = a.clone();
+        diff.difference_with(&b);
+        assert_eq!(diff.iter().collect::<Vec<_>>(), vec![Point::new(1, 1)]);
+        assert!(!a.is_subset(&b));
+    }
+
+    #[test]
+    #[should_panic(expected = "Sets are for different grid sizes")]
+    fn set_operations_need_the_same_grid() {
+        // (0, 1) and (8, 0) are both bit 8, so only the sizes tell them apart.
+        let mut a = GridBitSet::with_size(8, 8);
+        let mut b = GridBitSet::with_size(64, 1);
+        a.insert(&Point::new(0, 1));
+        b.insert(&Point::new(8, 0));
+        a.difference_with(&b);
+    }
+
+    #[test]
+    #[should_panic(expected = "Sets are for different grid sizes")]
+    fn direction_sets_need_the_same_grid() {
+        let a = DirBitSet::with_size(2, 3);
+        let b = DirBitSet::with_size(3, 2);
+        a.is_subset(&b);
+    }
+
+    #[test]
+    fn directions_are_tracked_per_cell() {
+        let mut s = DirBitSet::with_size(5, 5);
+        assert!(s.insert(&Point::new(1, 2), Dir4::Up));
+        assert!(s.insert(&Point::new(1, 2), Dir4::Left));
+        assert!(!s.insert(&Point::new(1, 2), Dir4::Up));
+        assert!(s.contains(&Point::new(1, 2), Dir4::Left));
+        assert!(!s.contains(&Point::new(1, 2), Dir4::Down));
+        assert_eq!(s.count(), 2);
+        assert_eq!(s.cells().count(), 1);
+        assert_eq!(
+            s.iter().collect::<Vec<_>>(),
+            vec![(Point::new(1, 2), Dir4::Up), (Point::new(1, 2), Dir4::Left)]
+        );
+        assert!(s.remove(&Point::new(1, 2), Dir4::Up));
+        assert!(!s.remove(&Point::new(1, 2), Dir4::Up));
+        s.clear();
+        assert!(s.is_empty());
+    }
+
+    #[test]
+    fn direction_set_operations() {
+        let g: Grid<char> = "...\n...".parse().unwrap();
+        let mut a = DirBitSet::new(&g);
+        let mut b = DirBitSet::new(&g);
+        a.insert(&Point::new(0, 0), Dir4::Up);
+        a.insert(&Point::new(2, 1), Dir4::Down);
+        b.insert(&Point::new(0, 0), Dir4::Up);
+        b.insert(&Point::new(0, 0), Dir4::Right);
+
+        let mut union = a.clone();
+        union.union_with(&b);
+        assert_eq!(union.count(), 3);
+        assert!(a.is_subset(&union) && b.is_subset(&union));
+
+        let mut both = a.clone();
+        both.intersect_with(&b);
+        assert_eq!(
+            both.iter().collect::<Vec<_>>(),
+            vec![(Point::new(0, 0), Dir4::Up)]
+        );
+
+        let mut only_a = a.clone();
+        only_a.difference_with(&b);
+        assert_eq!(
+            only_a.iter().collect::<Vec<_>>(),
+            vec![(Point::new(2, 1), Dir4::Down)]
+        );
+        assert!(!a.is_subset(&b));
+    }
+}
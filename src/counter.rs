@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// A multiset. Only ever holds non-zero counts, so `len` is the number of
+// distinct items actually present.
+#[derive(Clone, Debug)]
+pub struct Counter<T> {
+    counts: HashMap<T, usize>,
+}
+
+impl<T> Default for Counter<T> {
+    fn default() -> Self {
+        return Counter {
+            counts: HashMap::new(),
+        };
+    }
+}
+
+impl<T> PartialEq for Counter<T>
+where
+    T: Eq + Hash,
+{
+    fn eq(&self, other: &Self) -> bool {
+        return self.counts == other.counts;
+    }
+}
+
+impl<T> Eq for Counter<T> where T: Eq + Hash {}
+
+impl<T> FromIterator<T> for Counter<T>
+where
+    T: Eq + Hash,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut out = Counter::new();
+        for item in iter {
+            out.add(item);
+        }
+
+        return out;
+    }
+}
+
+impl<T> Counter<T>
+where
+    T: Eq + Hash,
+{
+    pub fn new() -> Counter<T> {
+        return Counter::default();
+    }
+
+    pub fn add(&mut self, item: T) {
+        self.add_n(item, 1);
+    }
+
+    pub fn add_n(&mut self, item: T, n: usize) {
+        if n > 0 {
+            *self.counts.entry(item).or_insert(0) += n;
+        }
+    }
+
+    // Takes away at most what's there. Returns how many were removed.
+    pub fn remove_n(&mut self, item: &T, n: usize) -> usize {
+        let Some(c) = self.counts.get_mut(item) else {
+            return 0;
+        };
+
+        let removed = n.min(*c);
+        *c -= removed;
+        if *c == 0 {
+            self.counts.remove(item);
+        }
+        return removed;
+    }
+
+    pub fn count(&self, item: &T) -> usize {
+        return self.counts.get(item).copied().unwrap_or(0);
+    }
+
+    // Distinct items.
+    pub fn len(&self) -> usize {
+        return self.counts.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.counts.is_empty();
+    }
+
+    // All items, duplicates included.
+    pub fn total(&self) -> usize {
+        return self.counts.values().sum();
+    }
+
+    // Unordered, see `by_count` for something stable.
+    pub fn iter(&self) -> impl Iterator<Item = (&T, usize)> + '_ {
+        return self.counts.iter().map(|(item, &c)| (item, c));
+    }
+}
+
+impl<T> Counter<T>
+where
+    T: Clone + Eq + Hash,
+{
+    // Counts add up.
+    pub fn sum(&self, other: &Counter<T>) -> Counter<T> {
+        let mut out = self.clone();
+        for (item, c) in other.iter() {
+            out.add_n(item.clone(), c);
+        }
+
+        return out;
+    }
+
+    // The smaller count of each item in both.
+    pub fn intersection(&self, other: &Counter<T>) -> Counter<T> {
+        let mut out = Counter::new();
+        for (item, c) in self.iter() {
+            out.add_n(item.clone(), c.min(other.count(item)));
+        }
+
+        return out;
+    }
+
+    // Counts subtract, stopping at zero.
+    pub fn difference(&self, other: &Counter<T>) -> Counter<T> {
+        let mut out = Counter::new();
+        for (item, c) in self.iter() {
+            out.add_n(item.clone(), c.saturating_sub(other.count(item)));
+        }
+
+        return out;
+    }
+}
+
+impl<T> Counter<T>
+where
+    T: Eq + Hash + Ord,
+{
+    // Most common first, ties broken by the smaller item.
+    pub fn by_count(&self) -> Vec<(&T, usize)> {
+        let mut out: Vec<(&T, usize)> = self.iter().collect();
+        out.sort_by(|(a, ca), (b, cb)| cb.cmp(ca).then(a.cmp(b)));
+        return out;
+    }
+
+    pub fn most_common(&self, k: usize) -> Vec<(&T, usize)> {
+        let mut out = self.by_count();
+        out.truncate(k);
+        return out;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::counter::Counter;
+
+    #[test]
+    fn counts() {
+        let c: Counter<char> = "mississippi".chars().collect();
+        assert_eq!(c.count(&'s'), 4);
+        assert_eq!(c.count(&'m'), 1);
+        assert_eq!(c.count(&'z'), 0);
+        assert_eq!(c.len(), 4);
+        assert_eq!(c.total(), 11);
+        assert!(Counter::<char>::new().is_empty());
+    }
+
+    #[test]
+    fn sorted_by_count() {
+        let c: Counter<char> = "mississippi".chars().collect();
+        assert_eq!(
+            c.by_count(),
+            vec![(&'i', 4), (&'s', 4), (&'p', 2), (&'m', 1)]
+        );
+        assert_eq!(c.most_common(1), vec![(&'i', 4)]);
+        assert_eq!(c.most_common(10).len(), 4);
+    }
+
+    #[test]
+    fn removing() {
+        let mut c: Counter<u32> = [1, 1, 2].into_iter().collect();
+        assert_eq!(c.remove_n(&1, 5), 2);
+        assert_eq!(c.remove_n(&3, 1), 0);
+        assert_eq!(c.len(), 1);
+        c.add_n(4, 0);
+        assert_eq!(c.count(&4), 0);
+        assert_eq!(c.len(), 1);
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a: Counter<u32> = [1, 1, 1, 2, 3].into_iter().collect();
+        let b: Counter<u32> = [1, 2, 2, 4].into_iter().collect();
+
+        let sum = a.sum(&b);
+        assert_eq!(sum.by_count(), vec![(&1, 4), (&2, 3), (&3, 1), (&4, 1)]);
+
+        let both = a.intersection(&b);
+        assert_eq!(both.by_count(), vec![(&1, 1), (&2, 1)]);
+
+        let diff = a.difference(&b);
+        assert_eq!(diff.by_count(), vec![(&1, 2), (&3, 1)]);
+        assert_eq!(diff.count(&2), 0);
+        assert_eq!(diff.len(), 2);
+        assert_eq!(b.difference(&b), Counter::new());
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// Every detector here takes a step function returning `None` once the
+// sequence terminates (e.g. a guard walking off the map), in which case there
+// is no cycle to report.
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Cycle {
+    // Index of the first state that is part of the cycle.
+    pub start: usize,
+    pub length: usize,
+}
+
+impl Cycle {
+    // Maps step `n` onto the earliest step that lands on the same state.
+    pub fn reduce(&self, n: usize) -> usize {
+        if n < self.start {
+            return n;
+        }
+
+        return self.start + (n - self.start) % self.length;
+    }
+}
+
+pub fn detect_hashed<S, F>(init: S, mut step: F) -> Option<Cycle>
+where
+    S: Clone + Eq + Hash,
+    F: FnMut(&S) -> Option<S>,
+{
+    let mut seen = HashMap::new();
+    let mut cur = init;
+    let mut idx = 0;
+    loop {
+        if let Some(&first) = seen.get(&cur) {
+            return Some(Cycle {
+                start: first,
+                length: idx - first,
+            });
+        }
+
+        let nxt = step(&cur)?;
+        seen.insert(cur, idx);
+        cur = nxt;
+        idx += 1;
+    }
+}
+
+pub fn detect_floyd<S, F>(init: S, mut step: F) -> Option<Cycle>
+where
+    S: Clone + Eq,
+    F: FnMut(&S) -> Option<S>,
+{
+    // Hare runs at twice the speed until they meet somewhere inside the cycle.
+    let mut tortoise = step(&init)?;
+    let mut hare = step(&tortoise)?;
+    while tortoise != hare {
+        tortoise = step(&tortoise)?;
+        hare = step(&hare)?;
+        hare = step(&hare)?;
+    }
+
+    // The meeting point is a multiple of the cycle length from the start, so
+    // walking both at the same speed from there and from `init` meets at the start.
+    let mut start = 0;
+    tortoise = init;
+    while tortoise != hare {
+        tortoise = step(&tortoise)?;
+        hare = step(&hare)?;
+        start += 1;
+    }
+
+    let mut length = 1;
+    hare = step(&tortoise)?;
+    while tortoise != hare {
+        hare = step(&hare)?;
+        length += 1;
+    }
+
+    return Some(Cycle { start, length });
+}
+
+pub fn detect_brent<S, F>(init: S, mut step: F) -> Option<Cycle>
+where
+    S: Clone + Eq,
+    F: FnMut(&S) -> Option<S>,
+{
+    // Teleport the tortoise to the hare at every power of two, which finds
+    // the length directly.
+    let mut power = 1;
+    let mut length = 1;
+    let mut tortoise = init.clone();
+    let mut hare = step(&init)?;
+    while tortoise != hare {
+        if power == length {
+            tortoise = hare.clone();
+            power *= 2;
+            length = 0;
+        }
+        hare = step(&hare)?;
+        length += 1;
+    }
+
+    // Then a hare `length` steps ahead meets the tortoise at the start.
+    let mut tortoise = init.clone();
+    let mut hare = init;
+    for _ in 0..length {
+        hare = step(&hare)?;
+    }
+
+    let mut start = 0;
+    while tortoise != hare {
+        tortoise = step(&tortoise)?;
+        hare = step(&hare)?;
+        start += 1;
+    }
+
+    return Some(Cycle { start, length });
+}
+
+// The state after `n` steps, without walking all of them once a cycle shows up.
+// `None` if the sequence terminates before reaching step `n`.
+pub fn nth_state<S, F>(init: S, mut step: F, n: usize) -> Option<S>
+where
+    S: Clone + Eq + Hash,
+    F: FnMut(&S) -> Option<S>,
+{
+    let mut history: Vec<S> = Vec::new();
+    let mut seen = HashMap::new();
+    let mut cur = init;
+    loop {
+        if history.len() == n {
+            return Some(cur);
+        }
+
+        if let Some(&first) = seen.get(&cur) {
+            let cycle = Cycle {
+                start: first,
+                length: history.len() - first,
+            };
+            return Some(history[cycle.reduce(n)].clone());
+        }
+
+        let nxt = step(&cur)?;
+        seen.insert(cur.clone(), history.len());
+        history.push(cur);
+        cur = nxt;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cycle::detect_brent;
+    use crate::cycle::detect_floyd;
+    use crate::cycle::detect_hashed;
+    use crate::cycle::nth_state;
+    use crate::cycle::Cycle;
+
+    // 0 -> 1 -> 2 -> 3 -> 4 -> 5 -> 2 -> ...
+    fn rho(n: &u32) -> Option<u32> {
+        return Some(if *n == 5 { 2 } else { n + 1 });
+    }
+
+    fn ends_at_ten(n: &u32) -> Option<u32> {
+        return if *n < 10 { Some(n + 1) } else { None };
+    }
+
+    #[test]
+    fn all_detectors_agree_on_rho() {
+        let expected = Some(Cycle {
+            start: 2,
+            length: 4,
+        });
+        assert_eq!(detect_hashed(0, rho), expected);
+        assert_eq!(detect_floyd(0, rho), expected);
+        assert_eq!(detect_brent(0, rho), expected);
+    }
+
+    #[test]
+    fn fixed_point_is_a_cycle_of_one() {
+        let expected = Some(Cycle {
+            start: 0,
+            length: 1,
+        });
+        assert_eq!(detect_hashed(7, |n| Some(*n)), expected);
+        assert_eq!(detect_floyd(7, |n| Some(*n)), expected);
+        assert_eq!(detect_brent(7, |n| Some(*n)), expected);
+    }
+
+    #[test]
+    fn terminating_sequence_has_no_cycle() {
+        assert_eq!(detect_hashed(0, ends_at_ten), None);
+        assert_eq!(detect_floyd(0, ends_at_ten), None);
+        assert_eq!(detect_brent(0, ends_at_ten), None);
+    }
+
+    #[test]
+    fn nth_state_skips_ahead() {
+        assert_eq!(nth_state(0, rho, 0), Some(0));
+        assert_eq!(nth_state(0, rho, 1), Some(1));
+        assert_eq!(nth_state(0, rho, 6), Some(2));
+        // (1_000_000_000 - 2) % 4 == 2
+        assert_eq!(nth_state(0, rho, 1_000_000_000), Some(4));
+    }
+
+    #[test]
+    fn nth_state_past_the_end() {
+        assert_eq!(nth_state(0, ends_at_ten, 10), Some(10));
+        assert_eq!(nth_state(0, ends_at_ten, 11), None);
+    }
+}
@@ -1,57 +1,47 @@
-use std::collections::HashMap;
+use crate::answer::Answer;
+use crate::counter::Counter;
+use crate::parse;
+use crate::tally::Overflow;
+use crate::tally::Tally;
+use crate::tally::Wide;
 
 const INPUT_1: &'static str = include_str!("day1-1.txt");
 
 fn parse_input1(s: &'static str) -> (Vec<u32>, Vec<u32>) {
-    s.split("\n")
-        .into_iter()
-        .filter_map(|line| {
-            let mut splits = line.split_whitespace();
-            let p1_1 = splits.next()?;
-            let p2_1 = splits.next()?;
-
-            let p1 = p1_1.parse::<u32>().unwrap();
-            let p2 = p2_1.parse::<u32>().unwrap();
-            return Some((p1, p2));
-        })
-        .unzip()
+    let [l1, l2] = parse::columns(s).unwrap_or_else(|e| panic!("Day 1 input, {}", e));
+    return (l1, l2);
 }
 
-fn freq_count(nums: &Vec<u32>) -> HashMap<u32, u32> {
-    let mut out_cnt = HashMap::new();
-    for n in nums.iter() {
-        if out_cnt.contains_key(n) {
-            let x = out_cnt.get_mut(n).unwrap();
-            *x = *x + 1;
-        } else {
-            out_cnt.insert(*n, 1);
-        }
-    }
+fn total_distance(mut l1: Vec<u32>, mut l2: Vec<u32>) -> Result<Wide, Overflow> {
+    l1.sort();
+    l2.sort();
 
-    return out_cnt;
+    let mut total = Tally::new(1, 1);
+    for (l, r) in l1.into_iter().zip(l2) {
+        total.add(l.abs_diff(r))?;
+    }
+    return Ok(total.total());
 }
 
-pub fn run_p1() {
-    let (mut l1, mut l2) = parse_input1(INPUT_1);
-    l1.sort();
-    l2.sort();
+fn similarity(l1: Vec<u32>, l2: Vec<u32>) -> Result<Wide, Overflow> {
+    let freq_count: Counter<u32> = l2.into_iter().collect();
+    let mut total = Tally::new(1, 2);
+    for n in l1 {
+        total.add_product(n, freq_count.count(&n) as u64)?;
+    }
+    return Ok(total.total());
+}
 
-    println!(
-        "Day 1, Part 1: {}",
-        l1.into_iter()
-            .zip(l2.into_iter())
-            .map(|(l, r)| l.abs_diff(r))
-            .fold(0, std::ops::Add::add)
-    );
+pub fn run_p1() -> Answer {
+    let (l1, l2) = parse_input1(INPUT_1);
+    return total_distance(l1, l2)
+        .unwrap_or_else(|e| panic!("{}", e))
+        .into();
 }
 
-pub fn run_p2() {
+pub fn run_p2() -> Answer {
     let (l1, l2) = parse_input1(INPUT_1);
-    let freq_count = freq_count(&l2);
-    println!(
-        "Day 1, Part 2: {}",
-        l1.into_iter().fold(0, |acc, nxt| {
-            acc + (nxt * freq_count.get(&nxt).unwrap_or(&0))
-        })
-    );
+    return similarity(l1, l2)
+        .unwrap_or_else(|e| panic!("{}", e))
+        .into();
 }
@@ -1,24 +1,22 @@
 use std::fmt::Display;
-use std::ops::Sub;
 use std::str::FromStr;
 
+use crate::answer::Answer;
+use crate::parse;
+use crate::parse::ParseError;
+
 const INPUT_1: &'static str = include_str!("day2-1.txt");
 
-type E = &'static str;
+type Level = i32;
 
 #[derive(Debug)]
-struct Report(Vec<u32>);
+struct Report(Vec<Level>);
 
 impl FromStr for Report {
-    type Err = E;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        return Ok(Report(
-            s.split_whitespace()
-                .map(u32::from_str)
-                .filter_map(Result::ok)
-                .collect(),
-        ));
+        return Ok(Report(parse::row(s)?));
     }
 }
 
@@ -28,105 +26,281 @@ enum Direction {
     Desc,
 }
 
-impl Direction {
-    fn is_fine<A: Display + Ord + Sub>(&self, n1: A, n2: A) -> bool
-    where
-        <A as Sub>::Output: Ord,
-        <A as Sub>::Output: From<u32>,
-    {
-        let diff = match self {
-            Direction::Asc => {
-                if n2 > n1 {
-                    n2 - n1
-                } else {
-                    return false;
-                }
-            },
-            Direction::Desc => {
-                if n1 > n2 {
-                    n1 - n2
-                } else {
-                    return false;
-                }
-            },
+// What makes a report safe: every step goes the same allowed way by between
+// `min_step` and `max_step`, after dropping at most `removals` levels.
+#[derive(Debug, Clone)]
+struct SafetyPolicy {
+    min_step: u32,
+    max_step: u32,
+    directions: &'static [Direction],
+    removals: usize,
+}
+
+impl SafetyPolicy {
+    const PART_1: SafetyPolicy = SafetyPolicy {
+        min_step: 1,
+        max_step: 3,
+        directions: &[Direction::Asc, Direction::Desc],
+        removals: 0,
+    };
+
+    const PART_2: SafetyPolicy = SafetyPolicy {
+        removals: 1,
+        ..SafetyPolicy::PART_1
+    };
+
+    fn is_fine(&self, dir: &Direction, n1: Level, n2: Level) -> bool {
+        let diff = match dir {
+            Direction::Asc => i64::from(n2) - i64::from(n1),
+            Direction::Desc => i64::from(n1) - i64::from(n2),
         };
 
-        return diff <= 3.into();
+        return diff >= self.min_step.into() && diff <= self.max_step.into();
+    }
+}
+
+impl Display for SafetyPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let directions: Vec<&str> = self
+            .directions
+            .iter()
+            .map(|d| match d {
+                Direction::Asc => "asc",
+                Direction::Desc => "desc",
+            })
+            .collect();
+        return write!(
+            f,
+            "steps {} to {}, {}, up to {} removed",
+            self.min_step,
+            self.max_step,
+            directions.join(" or "),
+            self.removals
+        );
     }
 }
 
 impl Report {
     fn is_safe(&self) -> bool {
-        // Trivial cases.
-        if self.0.len() == 0 || self.0.len() == 1 {
-            return true;
-        }
+        return self.is_safe_under(&SafetyPolicy::PART_1);
+    }
+
+    fn is_safe_with_dampener(&self) -> bool {
+        return self.is_safe_under(&SafetyPolicy::PART_2);
+    }
 
-        let mut prev = self.0[0];
-        if self.0[1] == prev {
-            // Not increasing or decreasing ...
-            return false;
+    fn is_safe_under(&self, policy: &SafetyPolicy) -> bool {
+        return policy.directions.iter().any(|dir| match policy.removals {
+            0 => self.first_bad(policy, dir, None).is_none(),
+            1 => {
+                self.first_bad(policy, dir, None).is_none()
+                    || self.single_removal(policy, dir).is_some()
+            },
+            k => self.fewest_removals(policy, dir, k) <= k,
+        });
+    }
+
+    // A level whose removal makes an unsafe report fine in `dir`.
+    // Whatever gets removed has to break up the first bad step, otherwise
+    // those two levels stay next to each other, so only two are worth trying.
+    fn single_removal(&self, policy: &SafetyPolicy, dir: &Direction) -> Option<usize> {
+        let i = self.first_bad(policy, dir, None)?;
+        return [i, i + 1]
+            .into_iter()
+            .find(|skip| self.first_bad(policy, dir, Some(*skip)).is_none());
+    }
+
+    // Why a report fails part 1, judged the way the puzzle reads it: the
+    // first step sets the direction. `None` for safe reports.
+    fn diagnose(&self) -> Option<Diagnosis> {
+        let policy = &SafetyPolicy::PART_1;
+        if self.is_safe_under(policy) {
+            return None;
         }
-        let dir = if prev > self.0[1] {
+
+        let first_dir = if self.0[1] < self.0[0] {
             Direction::Desc
         } else {
             Direction::Asc
         };
-
-        for nxt in &self.0[1..] {
-            if dir.is_fine(prev, *nxt) {
-                prev = *nxt;
+        let (idx, problem) = self.0.windows(2).enumerate().find_map(|(i, w)| {
+            let problem = if w[0] == w[1] {
+                Problem::ZeroStep
+            } else if (w[1] < w[0]) != matches!(first_dir, Direction::Desc) {
+                Problem::DirectionChange
+            } else if w[0].abs_diff(w[1]) > policy.max_step {
+                Problem::StepTooBig
             } else {
-                return false;
+                return None;
+            };
+            return Some((i + 1, problem));
+        })?;
+
+        return Some(Diagnosis {
+            idx,
+            problem,
+            dampened_by: policy
+                .directions
+                .iter()
+                .find_map(|dir| self.single_removal(policy, dir)),
+        });
+    }
+
+    // Index of the left level of the first bad step, pretending the level at
+    // `skip` isn't there.
+    fn first_bad(
+        &self,
+        policy: &SafetyPolicy,
+        dir: &Direction,
+        skip: Option<usize>,
+    ) -> Option<usize> {
+        let mut prev: Option<usize> = None;
+        for idx in 0..self.0.len() {
+            if Some(idx) == skip {
+                continue;
+            }
+            if let Some(p) = prev {
+                if !policy.is_fine(dir, self.0[p], self.0[idx]) {
+                    return Some(p);
+                }
             }
+            prev = Some(idx);
         }
 
-        return true;
+        return None;
     }
 
-    fn without(&self, idx: usize) -> Report {
-        let mut v = self.0.clone();
-        v.remove(idx);
-        return Report(v);
-    }
-
-    fn is_safe_with_dampener(&self) -> bool {
-        if self.is_safe() {
-            return true;
+    // Fewest levels to drop so every step left is fine, though anything over
+    // `limit` only comes back as some number over `limit`. O(n * limit).
+    fn fewest_removals(&self, policy: &SafetyPolicy, dir: &Direction, limit: usize) -> usize {
+        let n = self.0.len();
+        if n == 0 {
+            return 0;
         }
 
-        for i in 0..self.0.len() {
-            if self.without(i).is_safe() {
-                return true;
+        // `kept_until[j]`: fewest drops before j for a fine run ending on j.
+        // Only the last `limit + 1` levels are worth stepping from.
+        let mut kept_until = vec![0; n];
+        for j in 0..n {
+            kept_until[j] = j;
+            for i in j.saturating_sub(limit + 1)..j {
+                if policy.is_fine(dir, self.0[i], self.0[j]) {
+                    kept_until[j] = kept_until[j].min(kept_until[i] + (j - i - 1));
+                }
             }
         }
 
-        return false;
+        return (0..n).map(|j| kept_until[j] + (n - 1 - j)).min().unwrap();
     }
 }
 
-fn parse_input() -> Vec<Report> {
-    return INPUT_1
-        .lines()
-        .filter_map(|x| Report::from_str(x).ok())
-        .collect();
+#[derive(Debug, PartialEq, Eq)]
+enum Problem {
+    DirectionChange,
+    ZeroStep,
+    StepTooBig,
 }
 
-pub fn run_p1() {
-    let report_list = parse_input();
-    println!(
-        "Day 2, Part 1: {}",
-        report_list.into_iter().filter(Report::is_safe).count()
-    )
+#[derive(Debug, PartialEq, Eq)]
+struct Diagnosis {
+    // The level that breaks the first bad step.
+    idx: usize,
+    problem: Problem,
+    // The level the dampener drops to make it safe, if it can.
+    dampened_by: Option<usize>,
+}
+
+impl Display for Diagnosis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let problem = match self.problem {
+            Problem::DirectionChange => "direction change",
+            Problem::ZeroStep => "zero step",
+            Problem::StepTooBig => "step too big",
+        };
+        write!(f, "{} at index {}", problem, self.idx)?;
+        return match self.dampened_by {
+            Some(removed) => write!(f, ", safe without index {}", removed),
+            None => write!(f, ", dampener can't help"),
+        };
+    }
+}
+
+fn parse_input(s: &str) -> Result<Vec<Report>, ParseError> {
+    return Ok(parse::rows(s)?.into_iter().map(Report).collect());
 }
 
-pub fn run_p2() {
-    let report_list = parse_input();
+fn read_input() -> Vec<Report> {
+    return parse_input(INPUT_1).unwrap_or_else(|e| panic!("Day 2 input, {}", e));
+}
+
+pub fn run_p1() -> Answer {
+    let report_list = read_input();
+    return report_list
+        .into_iter()
+        .filter(Report::is_safe)
+        .count()
+        .into();
+}
+
+pub fn run_p2() -> Answer {
+    let report_list = read_input();
+    return report_list
+        .into_iter()
+        .filter(Report::is_safe_with_dampener)
+        .count()
+        .into();
+}
+
+// Every input line with its verdict, and why for the unsafe ones.
+pub fn run_audit() {
+    for (idx, line) in INPUT_1.lines().enumerate() {
+        let report = match Report::from_str(line) {
+            Ok(r) => r,
+            Err(e) => {
+                println!(
+                    "{:4} UNPARSED  {}  -- column {}: {}",
+                    idx + 1,
+                    line,
+                    e.col,
+                    e.reason
+                );
+                continue;
+            },
+        };
+        match report.diagnose() {
+            None => println!("{:4} SAFE      {}", idx + 1, line),
+            Some(d) => {
+                let verdict = if d.dampened_by.is_some() {
+                    "DAMPENED"
+                } else {
+                    "UNSAFE"
+                };
+                println!("{:4} {:9} {}  -- {}", idx + 1, verdict, line, d);
+            },
+        }
+    }
+}
+
+// Part 1 and 2 counts under a different policy, e.g. `1 3 either 2`.
+pub fn run_whatif(min_step: u32, max_step: u32, directions: &str, removals: usize) {
+    let policy = SafetyPolicy {
+        min_step,
+        max_step,
+        directions: match directions {
+            "asc" => &[Direction::Asc],
+            "desc" => &[Direction::Desc],
+            "either" => &[Direction::Asc, Direction::Desc],
+            _ => panic!("Directions are asc, desc or either"),
+        },
+        removals,
+    };
+    let report_list = read_input();
     println!(
-        "Day 2, Part 2: {}",
+        "Day 2, {}: {}",
+        policy,
         report_list
-            .into_iter()
-            .filter(Report::is_safe_with_dampener)
+            .iter()
+            .filter(|r| r.is_safe_under(&policy))
             .count()
     )
 }
@@ -166,7 +340,74 @@ mod test_p1 {
 
 #[cfg(test)]
 mod test_p2 {
+    use crate::day2::read_input;
+    use crate::day2::Level;
     use crate::day2::Report;
+    use crate::rng::xorshift;
+
+    // The original quadratic check, kept as a reference.
+    fn brute_force_dampener(r: &Report) -> bool {
+        return r.is_safe()
+            || (0..r.0.len()).any(|i| {
+                let mut v = r.0.clone();
+                v.remove(i);
+                return Report(v).is_safe();
+            });
+    }
+
+    fn assert_matches_brute_force(r: Report) {
+        assert_eq!(
+            r.is_safe_with_dampener(),
+            brute_force_dampener(&r),
+            "{:?}",
+            r
+        );
+    }
+
+    #[test]
+    fn matches_brute_force_on_cases() {
+        for levels in [
+            vec![61, 60, 51, 54, 51],
+            vec![61, 60, 51, 54, 49],
+            vec![53, 49, 52],
+            vec![53, 49, 42],
+            vec![5, 5, 7, 9, 12, 16],
+            vec![5, 5, 7, 9, 12, 13],
+            vec![5, 5, 5, 7, 9, 12, 16],
+            vec![5, 5, 7, 7, 12, 16],
+            vec![5, 4, 8, 12, 13],
+            vec![5, 4, 8, 9, 10],
+            vec![7, 9, 4, 1],
+            vec![432, 45, 44, 43, 42],
+            vec![45, 432, 46, 47, 48],
+            vec![28, 25, 26, 25, 24],
+            vec![],
+            vec![1],
+            vec![1, 1],
+        ] {
+            assert_matches_brute_force(Report(levels));
+        }
+        for r in read_input() {
+            assert_matches_brute_force(r);
+        }
+    }
+
+    #[test]
+    fn matches_brute_force_on_random_reports() {
+        let mut rand = xorshift(0x2024_1202);
+
+        for _ in 0..20_000 {
+            let len = rand(9) as usize;
+            let mut level = 10 + rand(10);
+            let mut levels = Vec::with_capacity(len);
+            for _ in 0..len {
+                levels.push(level);
+                // Mostly small steps in either direction, sometimes a big one.
+                level = (level + rand(9)).saturating_sub(4 + rand(2) * rand(5));
+            }
+            assert_matches_brute_force(Report(levels.into_iter().map(|l| l as Level).collect()));
+        }
+    }
 
     #[test]
     fn report_is_bad_up_and_down_same_damper() {
@@ -297,3 +538,228 @@ mod test_p2 {
         assert!(Report(vec![1, 3, 6, 7, 9]).is_safe_with_dampener());
     }
 }
+
+#[cfg(test)]
+mod test_policy {
+    use crate::day2::Direction;
+    use crate::day2::Level;
+    use crate::day2::Report;
+    use crate::day2::SafetyPolicy;
+    use crate::rng::xorshift;
+
+    // Tries every subset of levels to keep.
+    fn brute_force(r: &Report, policy: &SafetyPolicy) -> bool {
+        let n = r.0.len();
+        return (0u32..1 << n).any(|keep| {
+            if n - keep.count_ones() as usize > policy.removals {
+                return false;
+            }
+            let kept: Vec<Level> = (0..n)
+                .filter(|i| keep & (1 << i) != 0)
+                .map(|i| r.0[i])
+                .collect();
+            return policy
+                .directions
+                .iter()
+                .any(|dir| kept.windows(2).all(|w| policy.is_fine(dir, w[0], w[1])));
+        });
+    }
+
+    #[test]
+    fn two_removals() {
+        let policy = SafetyPolicy {
+            removals: 2,
+            ..SafetyPolicy::PART_1
+        };
+        assert!(Report(vec![5, 5, 5, 7, 9, 12]).is_safe_under(&policy));
+        assert!(Report(vec![1, 9, 2, 9, 3]).is_safe_under(&policy));
+        assert!(!Report(vec![1, 9, 2, 9, 3, 9]).is_safe_under(&policy));
+        assert!(Report(vec![9, 1, 9]).is_safe_under(&policy));
+    }
+
+    #[test]
+    fn one_direction_only() {
+        let asc = SafetyPolicy {
+            directions: &[Direction::Asc],
+            ..SafetyPolicy::PART_2
+        };
+        assert!(Report(vec![1, 2, 3]).is_safe_under(&asc));
+        assert!(Report(vec![3, 1, 2, 3]).is_safe_under(&asc));
+        assert!(!Report(vec![3, 2, 1]).is_safe_under(&asc));
+        assert_eq!(asc.to_string(), "steps 1 to 3, asc, up to 1 removed");
+        assert_eq!(
+            SafetyPolicy::PART_1.to_string(),
+            "steps 1 to 3, asc or desc, up to 0 removed"
+        );
+    }
+
+    #[test]
+    fn step_bounds() {
+        let wide = SafetyPolicy {
+            min_step: 0,
+            max_step: 10,
+            ..SafetyPolicy::PART_1
+        };
+        assert!(Report(vec![1, 1, 11, 11]).is_safe_under(&wide));
+        assert!(!Report(vec![1, 12]).is_safe_under(&wide));
+        let exact = SafetyPolicy {
+            min_step: 2,
+            max_step: 2,
+            ..SafetyPolicy::PART_1
+        };
+        assert!(Report(vec![8, 6, 4]).is_safe_under(&exact));
+        assert!(!Report(vec![8, 7, 6]).is_safe_under(&exact));
+    }
+
+    #[test]
+    fn matches_brute_force_for_any_k() {
+        let mut rand = xorshift(0x0202_2024);
+
+        let direction_sets: [&'static [Direction]; 3] = [
+            &[Direction::Asc],
+            &[Direction::Desc],
+            &[Direction::Asc, Direction::Desc],
+        ];
+        for _ in 0..5_000 {
+            let min_step = rand(3);
+            let policy = SafetyPolicy {
+                min_step,
+                max_step: min_step + rand(4),
+                directions: direction_sets[rand(3) as usize],
+                removals: rand(5) as usize,
+            };
+            // Centred on zero, so negative levels get a look in too.
+            let levels: Vec<Level> = (0..rand(10)).map(|_| rand(12) as Level - 6).collect();
+            let r = Report(levels);
+            assert_eq!(
+                r.is_safe_under(&policy),
+                brute_force(&r, &policy),
+                "{:?} {:?}",
+                r,
+                policy
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_diagnose {
+    use crate::day2::read_input;
+    use crate::day2::Diagnosis;
+    use crate::day2::Problem;
+    use crate::day2::Report;
+
+    fn diagnosis(idx: usize, problem: Problem, dampened_by: Option<usize>) -> Option<Diagnosis> {
+        return Some(Diagnosis {
+            idx,
+            problem,
+            dampened_by,
+        });
+    }
+
+    #[test]
+    fn sample_examples() {
+        assert_eq!(Report(vec![7, 6, 4, 2, 1]).diagnose(), None);
+        assert_eq!(
+            Report(vec![1, 2, 7, 8, 9]).diagnose(),
+            diagnosis(2, Problem::StepTooBig, None)
+        );
+        assert_eq!(
+            Report(vec![9, 7, 6, 2, 1]).diagnose(),
+            diagnosis(3, Problem::StepTooBig, None)
+        );
+        assert_eq!(
+            Report(vec![1, 3, 2, 4, 5]).diagnose(),
+            diagnosis(2, Problem::DirectionChange, Some(1))
+        );
+        assert_eq!(
+            Report(vec![8, 6, 4, 4, 1]).diagnose(),
+            diagnosis(3, Problem::ZeroStep, Some(2))
+        );
+    }
+
+    #[test]
+    fn first_step_problems() {
+        assert_eq!(
+            Report(vec![5, 5, 6]).diagnose(),
+            diagnosis(1, Problem::ZeroStep, Some(0))
+        );
+        assert_eq!(
+            Report(vec![432, 45, 44, 43]).diagnose(),
+            diagnosis(1, Problem::StepTooBig, Some(0))
+        );
+        assert_eq!(
+            Report(vec![28, 25, 26, 25, 24])
+                .diagnose()
+                .unwrap()
+                .to_string(),
+            "direction change at index 2, safe without index 1"
+        );
+    }
+
+    #[test]
+    fn agrees_with_checks_on_input() {
+        for r in read_input() {
+            let d = r.diagnose();
+            assert_eq!(d.is_none(), r.is_safe(), "{:?}", r);
+            let Some(d) = d else {
+                continue;
+            };
+            assert_eq!(
+                d.dampened_by.is_some(),
+                r.is_safe_with_dampener(),
+                "{:?}",
+                r
+            );
+            if let Some(removed) = d.dampened_by {
+                let mut v = r.0.clone();
+                v.remove(removed);
+                assert!(Report(v).is_safe(), "{:?} without {}", r, removed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_parse {
+    use crate::day2::parse_input;
+    use crate::day2::Report;
+    use crate::parse::ParseError;
+
+    #[test]
+    fn bad_token_is_not_skipped() {
+        assert_eq!(
+            "7 6 x 2".parse::<Report>().unwrap_err(),
+            ParseError {
+                line: 1,
+                col: 5,
+                reason: "Not a number",
+            }
+        );
+        assert_eq!("7  6 2.5".parse::<Report>().unwrap_err().col, 6);
+        assert_eq!("".parse::<Report>().unwrap_err().reason, "Empty row");
+    }
+
+    #[test]
+    fn negative_levels() {
+        let r: Report = "-1 -3 -4 -6".parse().unwrap();
+        assert_eq!(r.0, vec![-1, -3, -4, -6]);
+        assert!(r.is_safe());
+        assert!(Report(vec![-1, 1, 2]).is_safe());
+    }
+
+    #[test]
+    fn input_errors_have_lines() {
+        assert_eq!(parse_input("1 2 3\n4 5 6").unwrap().len(), 2);
+        assert_eq!(
+            parse_input("1 2 3\n\n4 5 6").unwrap_err().to_string(),
+            "line 2, column 1: Empty row"
+        );
+        assert_eq!(
+            parse_input("1 2 3\n4 5 6\n7 6 x 2")
+                .unwrap_err()
+                .to_string(),
+            "line 3, column 5: Not a number"
+        );
+    }
+}
@@ -1,164 +1,358 @@
-use std::iter::Peekable;
+use std::fmt::Write;
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::ErrorKind;
+use std::ops::Range;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::answer::Answer;
+use crate::day3::char_search::MulSearcher;
+use crate::rng::xorshift;
+use crate::tally::Overflow;
+use crate::tally::Tally;
+use crate::tally::Wide;
+
+mod char_search;
 
 const INPUT_1: &'static str = include_str!("day3-1.txt");
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 struct Mul(u32, u32);
 
 impl Mul {
-    fn compute(&self) -> u32 {
-        return self.0 * self.1;
+    // Two u32s always fit, no need to check.
+    fn compute(&self) -> Wide {
+        return self.0 as Wide * self.1 as Wide;
     }
 }
 
-struct MulSearcher<It>
-where
-    It: Iterator,
-{
-    inner_iter: Peekable<It>,
-    cond: CondBehavior,
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Instr {
+    Mul(Mul),
+    Do,
+    Dont,
 }
 
-impl<T> MulSearcher<T>
-where
-    T: Iterator,
-{
-    fn new(i: T, cond: CondBehavior) -> MulSearcher<T> {
-        MulSearcher {
-            inner_iter: i.peekable(),
-            cond,
+// Enough for anything registered so far, raise it if something needs more.
+const MAX_ARITY: usize = 2;
+
+// How an instruction is written: `name(` then `arity` arguments split by
+// `separator`, then `)`. Each argument is 1 to `max_digits` decimal digits,
+// at most 9 so it always fits a u32. `InstrTable::new` checks all that.
+struct InstrSpec {
+    name: &'static str,
+    arity: usize,
+    max_digits: usize,
+    // Only needed with more than one argument.
+    separator: Option<u8>,
+    build: fn(&[u32]) -> Instr,
+}
+
+const MUL: InstrSpec = InstrSpec {
+    name: "mul",
+    arity: 2,
+    max_digits: 3,
+    separator: Some(b','),
+    build: |args| Instr::Mul(Mul(args[0], args[1])),
+};
+
+const DO: InstrSpec = InstrSpec {
+    name: "do",
+    arity: 0,
+    max_digits: 0,
+    separator: None,
+    build: |_| Instr::Do,
+};
+
+const DONT: InstrSpec = InstrSpec {
+    name: "don't",
+    arity: 0,
+    max_digits: 0,
+    separator: None,
+    build: |_| Instr::Dont,
+};
+
+const INSTRUCTIONS: &'static [InstrSpec] = &[MUL, DO, DONT];
+
+impl InstrSpec {
+    fn check(&self) -> Result<(), &'static str> {
+        if self.name.is_empty() {
+            return Err("Empty instruction name");
+        }
+        if self.arity > MAX_ARITY {
+            return Err("Too many arguments");
+        }
+        if self.max_digits > 9 {
+            return Err("Arguments could overflow");
         }
+        if self.arity > 1 && self.separator.is_none() {
+            return Err("Arguments need a separator");
+        }
+        return Ok(());
     }
-}
 
-fn take_through<I>(char_iter: &mut Peekable<I>, nxt: &[char]) -> bool
-where
-    I: Iterator<Item = char>,
-{
-    for expected in nxt.iter() {
-        if let Some(actual) = char_iter.peek() {
-            if *actual == *expected {
-                // Actually eat this one.
-                char_iter.next();
-            } else {
-                return false;
+    // The instruction at the very start of `s`, and how many bytes it took.
+    fn match_at(&self, s: &[u8]) -> Option<(Instr, usize)> {
+        let mut pos = self.name.len();
+        if !s.starts_with(self.name.as_bytes()) || s.get(pos) != Some(&b'(') {
+            return None;
+        }
+        pos += 1;
+
+        let mut args = [0; MAX_ARITY];
+        for (idx, arg) in args.iter_mut().take(self.arity).enumerate() {
+            if idx > 0 {
+                if s.get(pos).copied() != self.separator {
+                    return None;
+                }
+                pos += 1;
             }
-        } else {
-            // Reached the end.
-            return false;
+
+            let digits = s[pos..].iter().take_while(|b| b.is_ascii_digit()).count();
+            if digits == 0 || digits > self.max_digits {
+                return None;
+            }
+            for d in &s[pos..pos + digits] {
+                *arg = *arg * 10 + (d - b'0') as u32;
+            }
+            pos += digits;
+        }
+
+        if s.get(pos) != Some(&b')') {
+            return None;
         }
+        return Some(((self.build)(&args[..self.arity]), pos + 1));
     }
+}
 
-    return true;
+// An instruction and the bytes of the input it came from.
+#[derive(Debug, PartialEq, Eq)]
+struct Token {
+    instr: Instr,
+    span: Range<usize>,
 }
 
-// Returns None if the next character is not a digit, or if the number
-// of sequential digits is > 3
-fn read_digit_until<I>(char_iter: &mut Peekable<I>, marker: char) -> Option<u32>
-where
-    I: Iterator<Item = char>,
-{
-    let mut acc = String::new();
-    loop {
-        match char_iter.peek() {
-            Some(d) if d.is_digit(10) => {
-                // Eat the digit
-                acc.extend(std::iter::once(d));
-            },
-            Some(c) if *c == marker => {
-                break;
-            },
-            // Either it wasn't a digit, or we reached the end.
-            Some(_) | None => {
-                return None;
-            },
+// How `InstrTable::find` gets from one candidate position to the next.
+#[derive(Clone, Debug)]
+enum Skip {
+    // Tries every position, only there to compare against.
+    EveryByte,
+    // Looks each byte up in `starts`.
+    Table,
+    // Checks eight bytes at a time for any of a few start bytes, each one
+    // repeated across a word. Only worth it with a handful of them.
+    Words(Vec<u64>),
+}
+
+// More start bytes than this and the table is quicker.
+const MAX_WORD_NEEDLES: usize = 3;
+
+// The registered instructions, plus which bytes are worth trying at all.
+struct InstrTable<'a> {
+    specs: &'a [InstrSpec],
+    // Bytes some instruction starts with, nothing else can match.
+    starts: [bool; 256],
+    skip: Skip,
+}
+
+impl<'a> InstrTable<'a> {
+    fn new(specs: &'a [InstrSpec]) -> InstrTable<'a> {
+        let mut starts = [false; 256];
+        for spec in specs {
+            if let Err(reason) = spec.check() {
+                panic!("Can't register {:?}: {}", spec.name, reason);
+            }
+            starts[spec.name.as_bytes()[0] as usize] = true;
         }
-        char_iter.next();
+
+        let needles: Vec<u64> = (0..=255u8)
+            .filter(|b| starts[*b as usize])
+            .map(|b| u64::from_ne_bytes([b; 8]))
+            .collect();
+        let skip = if needles.len() <= MAX_WORD_NEEDLES {
+            Skip::Words(needles)
+        } else {
+            Skip::Table
+        };
+        return InstrTable {
+            specs,
+            starts,
+            skip,
+        };
     }
 
-    // Getting here means we found the terminal marker, but we still need to eat it.
-    // Can't .next inside match bc of borrow for peek()
-    char_iter.next();
-    if acc.len() > 3 {
+    // Offset of the first byte in `s` some instruction could start with.
+    fn next_start(&self, s: &[u8]) -> Option<usize> {
+        return match &self.skip {
+            Skip::EveryByte => (!s.is_empty()).then_some(0),
+            Skip::Table => s.iter().position(|b| self.starts[*b as usize]),
+            Skip::Words(needles) => {
+                let mut words = s.chunks_exact(8);
+                for (idx, word) in words.by_ref().enumerate() {
+                    if let Some(at) = first_match(word.try_into().unwrap(), needles) {
+                        return Some(idx * 8 + at);
+                    }
+                }
+                let done = s.len() - words.remainder().len();
+                words
+                    .remainder()
+                    .iter()
+                    .position(|b| self.starts[*b as usize])
+                    .map(|at| done + at)
+            },
+        };
+    }
+
+    // Bytes needed after a position to be sure whether anything starts there.
+    fn lookahead(&self) -> usize {
+        return self
+            .specs
+            .iter()
+            .map(|s| s.name.len() + 2 + s.arity * s.max_digits + s.arity.saturating_sub(1))
+            .max()
+            .unwrap_or(1);
+    }
+
+    // The first instruction starting at `*pos` or later but before `end`,
+    // leaving `*pos` just past it. With nothing found `*pos` ends up at `end`.
+    // The first spec to match at a position wins.
+    fn find(&self, input: &[u8], pos: &mut usize, end: usize) -> Option<Token> {
+        while *pos < end {
+            // Most of the input is corruption, so hop straight to the next
+            // byte that could begin an instruction.
+            match self.next_start(&input[*pos..end]) {
+                Some(skip) => *pos += skip,
+                None => break,
+            }
+
+            for spec in self.specs {
+                if let Some((instr, len)) = spec.match_at(&input[*pos..]) {
+                    let span = *pos..*pos + len;
+                    *pos += len;
+                    return Some(Token { instr, span });
+                }
+            }
+            *pos += 1;
+        }
+
+        *pos = end.max(*pos);
         return None;
-    } else {
-        return acc.parse().ok();
     }
 }
 
-impl<T> Iterator for MulSearcher<T>
+// Index of the first of the eight bytes equal to any needle, a needle being
+// one byte repeated across a word. XOR turns a matching byte into zero, which
+// the usual subtract-and-mask trick flags. A borrow out of a zero can also
+// flag the byte after it, but never one before, so the lowest flag is right.
+fn first_match(word: [u8; 8], needles: &[u64]) -> Option<usize> {
+    const ONES: u64 = u64::from_ne_bytes([0x01; 8]);
+    const HIGHS: u64 = u64::from_ne_bytes([0x80; 8]);
+
+    // Little-endian so the lowest bits are the earliest byte.
+    let word = u64::from_le_bytes(word);
+    let mut found = 0;
+    for needle in needles {
+        let zeroed = word ^ needle;
+        found |= zeroed.wrapping_sub(ONES) & !zeroed & HIGHS;
+    }
+    return (found != 0).then(|| found.trailing_zeros() as usize / 8);
+}
+
+// Finds registered instructions anywhere in the input, skipping whatever
+// corruption is around them. Scanning carries on after each one.
+struct Scanner<'a> {
+    input: &'a [u8],
+    pos: usize,
+    table: InstrTable<'a>,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(input: &'a str, specs: &'a [InstrSpec]) -> Scanner<'a> {
+        return Scanner {
+            input: input.as_bytes(),
+            pos: 0,
+            table: InstrTable::new(specs),
+        };
+    }
+}
+
+impl Iterator for Scanner<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        return self.table.find(self.input, &mut self.pos, self.input.len());
+    }
+}
+
+// The same as `Scanner`, but reading as it goes. Only holds what the reader
+// buffers plus a few bytes carried over, in case an instruction straddles
+// two reads. Spans are offsets into the whole stream.
+struct StreamScanner<'a, R> {
+    reader: R,
+    buf: Vec<u8>,
+    // Where `buf` starts in the stream.
+    base: usize,
+    pos: usize,
+    eof: bool,
+    table: InstrTable<'a>,
+}
+
+impl<'a, R> StreamScanner<'a, R>
+where
+    R: BufRead,
+{
+    fn new(reader: R, specs: &'a [InstrSpec]) -> StreamScanner<'a, R> {
+        return StreamScanner {
+            reader,
+            buf: Vec::new(),
+            base: 0,
+            pos: 0,
+            eof: false,
+            table: InstrTable::new(specs),
+        };
+    }
+}
+
+impl<R> Iterator for StreamScanner<'_, R>
 where
-    T: Iterator<Item = char>,
+    R: BufRead,
 {
-    type Item = Mul;
+    type Item = std::io::Result<Token>;
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            match self.inner_iter.next() {
-                Some('m') => {
-                    if !take_through(&mut self.inner_iter, &['u', 'l', '(']) {
-                        continue;
-                    }
-
-                    // Now we parse digits ...
-                    let left = match read_digit_until(&mut self.inner_iter, ',') {
-                        Some(n) => n,
-                        None => {
-                            continue;
-                        },
-                    };
-
-                    let right = match read_digit_until(&mut self.inner_iter, ')') {
-                        Some(n) => n,
-                        None => {
-                            continue;
-                        },
-                    };
+            // Until the end, only try positions with a whole instruction's
+            // worth of bytes after them.
+            let end = if self.eof {
+                self.buf.len()
+            } else {
+                self.buf.len().saturating_sub(self.table.lookahead() - 1)
+            };
+            if let Some(t) = self.table.find(&self.buf, &mut self.pos, end) {
+                let span = self.base + t.span.start..self.base + t.span.end;
+                return Some(Ok(Token { span, ..t }));
+            }
+            if self.eof {
+                return None;
+            }
 
-                    // Skip while not included.
-                    if self.cond.should_include() {
-                        return Some(Mul(left, right));
-                    }
-                },
-                Some('d') => {
-                    if !take_through(&mut self.inner_iter, &['o']) {
-                        continue;
-                    }
+            self.buf.drain(..self.pos);
+            self.base += self.pos;
+            self.pos = 0;
 
-                    let nxt_peek = if let Some(c) = self.inner_iter.peek() {
-                        *c
-                    } else {
-                        continue;
-                    };
-                    match nxt_peek {
-                        'n' => {
-                            // Take the char
-                            self.inner_iter.next();
-                            if !take_through(&mut self.inner_iter, &['\'', 't', '(', ')']) {
-                                continue;
-                            }
-                            self.cond.set(false);
-                        },
-                        '(' => {
-                            // Take the char
-                            self.inner_iter.next();
-                            if !take_through(&mut self.inner_iter, &[')']) {
-                                continue;
-                            }
-                            self.cond.set(true);
-                        },
-                        _ => {
-                            continue;
-                        },
-                    }
-                },
-                // We're just looking for 'm' to start a mul
-                Some(_) => {
-                    continue;
-                },
-                None => {
-                    return None;
-                },
+            let chunk = match self.reader.fill_buf() {
+                Ok(chunk) => chunk,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Some(Err(e)),
+            };
+            if chunk.is_empty() {
+                self.eof = true;
+            } else {
+                let read = chunk.len();
+                self.buf.extend_from_slice(chunk);
+                self.reader.consume(read);
             }
         }
     }
@@ -183,27 +377,665 @@ impl CondBehavior {
             CondBehavior::Enabled(_) => CondBehavior::Enabled(b),
         }
     }
+
+    // Follows the instruction, handing back the mul if it counts.
+    fn apply(&mut self, instr: Instr) -> Option<Mul> {
+        return match instr {
+            Instr::Mul(m) => self.should_include().then_some(m),
+            Instr::Do => {
+                self.set(true);
+                None
+            },
+            Instr::Dont => {
+                self.set(false);
+                None
+            },
+        };
+    }
+}
+
+// Terminal colours for `run_annotate`.
+const COUNTED: &'static str = "\x1b[1;32m";
+const SKIPPED: &'static str = "\x1b[1;31m";
+const SWITCH: &'static str = "\x1b[1;36m";
+const DIM: &'static str = "\x1b[2m";
+const RESET: &'static str = "\x1b[0m";
+
+// An instruction found in the input, and what part 2 made of it.
+#[derive(Debug, PartialEq, Eq)]
+struct Audited {
+    token: Token,
+    // Whether a mul was counted, `None` for `do()` and `don't()`.
+    counted: Option<bool>,
+    // Whether muls are switched on once this instruction is done.
+    enabled: bool,
 }
 
-fn read_muls(
-    chars: impl Iterator<Item = char>,
-    cond_behavior: CondBehavior,
-) -> impl Iterator<Item = Mul> {
-    MulSearcher::new(chars, cond_behavior)
+fn audit(input: &str) -> Vec<Audited> {
+    let mut cond = CondBehavior::Enabled(true);
+    return Scanner::new(input, INSTRUCTIONS)
+        .map(|token| {
+            let applied = cond.apply(token.instr);
+            let counted = match token.instr {
+                Instr::Mul(_) => Some(applied.is_some()),
+                Instr::Do | Instr::Dont => None,
+            };
+            return Audited {
+                token,
+                counted,
+                enabled: cond.should_include(),
+            };
+        })
+        .collect();
 }
 
-pub fn run_p1() {
-    let mut total = 0;
-    for found_mul in read_muls(INPUT_1.chars(), CondBehavior::Ignore) {
-        total += found_mul.compute();
+// The input with every instruction coloured, and the corruption between them
+// dimmed wherever muls are switched off.
+fn highlight(input: &str, audited: &[Audited]) -> String {
+    let mut out = String::new();
+    let mut enabled = true;
+    let mut last = 0;
+    for a in audited {
+        let gap = &input[last..a.token.span.start];
+        if enabled || gap.is_empty() {
+            out.push_str(gap);
+        } else {
+            write!(out, "{}{}{}", DIM, gap, RESET).unwrap();
+        }
+
+        let style = match a.counted {
+            Some(true) => COUNTED,
+            Some(false) => SKIPPED,
+            None => SWITCH,
+        };
+        write!(out, "{}{}{}", style, &input[a.token.span.clone()], RESET).unwrap();
+        enabled = a.enabled;
+        last = a.token.span.end;
     }
-    println!("Day 3, Part 1: {}", total);
+
+    let tail = &input[last..];
+    if enabled || tail.is_empty() {
+        out.push_str(tail);
+    } else {
+        write!(out, "{}{}{}", DIM, tail, RESET).unwrap();
+    }
+    return out;
 }
 
-pub fn run_p2() {
+// A line per instruction: where it is, what it is, and what part 2 made of it.
+fn summary(input: &str, audited: &[Audited]) -> String {
+    let mut out = String::new();
+    for a in audited {
+        let verdict = match (a.token.instr, a.counted) {
+            (Instr::Mul(m), Some(true)) => format!("counted, {}", m.compute()),
+            (Instr::Mul(_), _) => "skipped, disabled".to_string(),
+            (Instr::Do, _) => "enables".to_string(),
+            (Instr::Dont, _) => "disables".to_string(),
+        };
+        let text = &input[a.token.span.clone()];
+        writeln!(out, "{:>8} {:<12} {}", a.token.span.start, text, verdict).unwrap();
+    }
+
+    let muls = audited.iter().filter(|a| a.counted.is_some()).count();
+    let skipped = audited.iter().filter(|a| a.counted == Some(false)).count();
+    writeln!(
+        out,
+        "{} muls, {} counted in part 2, {} skipped",
+        muls,
+        muls - skipped,
+        skipped
+    )
+    .unwrap();
+    return out;
+}
+
+// Corrupted memory much like the real input, `len` bytes give or take an
+// instruction.
+fn corrupted_memory(len: usize, seed: u32) -> String {
+    let mut rand = xorshift(seed);
+
+    const JUNK: &'static [u8] = b"mul(),don't0123456789 !@#$%^&*[]{}<>?/+-_'select'from'who'";
+    let mut out = String::with_capacity(len + 16);
+    while out.len() < len {
+        let piece = match rand(20) {
+            0..=1 => format!("mul({},{})", rand(1000), rand(1000)),
+            2 => "do()".to_string(),
+            3 => "don't()".to_string(),
+            // Something that nearly parses.
+            4..=5 => {
+                let full = format!("mul({},{})don't()", rand(10_000), rand(1000));
+                full[..1 + rand(full.len() as u32 - 1) as usize].to_string()
+            },
+            _ => (JUNK[rand(JUNK.len() as u32) as usize] as char).to_string(),
+        };
+        out.push_str(&piece);
+    }
+
+    return out;
+}
+
+fn read_muls(input: &str, cond_behavior: CondBehavior) -> impl Iterator<Item = Mul> + '_ {
+    let mut cond = cond_behavior;
+    return Scanner::new(input, INSTRUCTIONS).filter_map(move |t| cond.apply(t.instr));
+}
+
+fn sum_muls(muls: impl Iterator<Item = Mul>, part: u8) -> Result<Wide, Overflow> {
+    let mut total = Tally::new(3, part);
+    for found_mul in muls {
+        total.add(found_mul.compute())?;
+    }
+    return Ok(total.total());
+}
+
+pub fn run_p1() -> Answer {
+    let muls = read_muls(INPUT_1, CondBehavior::Ignore);
+    return sum_muls(muls, 1).unwrap_or_else(|e| panic!("{}", e)).into();
+}
+
+pub fn run_p2() -> Answer {
+    let muls = read_muls(INPUT_1, CondBehavior::Enabled(true));
+    return sum_muls(muls, 2).unwrap_or_else(|e| panic!("{}", e)).into();
+}
+
+// Part 2's view of the input, to see why it differs from part 1.
+pub fn run_annotate() {
+    let audited = audit(INPUT_1);
+    println!("{}", highlight(INPUT_1, &audited));
+    print!("{}", summary(INPUT_1, &audited));
+}
+
+// Both parts in one pass over a file, or stdin without one.
+pub fn run_stream(path: Option<&str>) {
+    let reader: Box<dyn BufRead> = match path {
+        Some(path) => Box::new(BufReader::new(
+            File::open(path).unwrap_or_else(|e| panic!("{}: {}", path, e)),
+        )),
+        None => Box::new(std::io::stdin().lock()),
+    };
+
+    let mut p1 = Tally::new(3, 1);
+    let mut p2 = Tally::new(3, 2);
+    let mut cond = CondBehavior::Enabled(true);
+    for token in StreamScanner::new(reader, INSTRUCTIONS) {
+        let instr = token.unwrap_or_else(|e| panic!("Day 3 input, {}", e)).instr;
+        let counted = cond.apply(instr);
+        if let Instr::Mul(m) = instr {
+            p1.add(m.compute()).unwrap_or_else(|e| panic!("{}", e));
+        }
+        if let Some(m) = counted {
+            p2.add(m.compute()).unwrap_or_else(|e| panic!("{}", e));
+        }
+    }
+
+    println!("Day 3, Part 1: {}", p1.total());
+    println!("Day 3, Part 2: {}", p2.total());
+}
+
+// What `run_bench` scans.
+pub enum BenchInput<'a> {
+    // This many MiB of `corrupted_memory`, saved so the run can be repeated.
+    Generate(usize),
+    // Whatever's in the file, say one an earlier run saved.
+    File(&'a str),
+}
+
+// The quickest of a few runs, and what it added up to.
+fn best_of(mut run: impl FnMut() -> Wide) -> (Wide, Duration) {
+    let mut best = Duration::MAX;
     let mut total = 0;
-    for found_mul in read_muls(INPUT_1.chars(), CondBehavior::Enabled(true)) {
-        total += found_mul.compute();
+    for _ in 0..5 {
+        let start = Instant::now();
+        total = run();
+        best = best.min(start.elapsed());
+    }
+    return (total, best);
+}
+
+// Times part 2 with the old char-by-char searcher, then with the byte scanner
+// under each way of skipping ahead.
+pub fn run_bench(source: BenchInput) {
+    let input = match source {
+        BenchInput::Generate(megabytes) => {
+            let input = corrupted_memory(megabytes << 20, 0x5EED);
+            let path = std::env::temp_dir().join(format!("day3-bench-{}MiB.txt", megabytes));
+            std::fs::write(&path, &input).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+            println!("Saved to {}, pass that to bench it again", path.display());
+            input
+        },
+        BenchInput::File(path) => {
+            std::fs::read_to_string(path).unwrap_or_else(|e| panic!("{}: {}", path, e))
+        },
+    };
+    let megabytes = input.len() as f64 / (1 << 20) as f64;
+
+    let (expected, chars) = best_of(|| {
+        return MulSearcher::new(input.chars(), CondBehavior::Enabled(true))
+            .map(|m| m.compute())
+            .sum();
+    });
+    println!("{:.1} MiB, part 2 is {}", megabytes, expected);
+    let report = |name: &str, took: Duration| {
+        println!(
+            "{:<10}  {:>12?}  {:>6.0} MiB/s  {:>5.1}x",
+            name,
+            took,
+            megabytes / took.as_secs_f64(),
+            chars.as_secs_f64() / took.as_secs_f64()
+        );
+    };
+    report("chars", chars);
+
+    for (name, skip) in [
+        ("every byte", Skip::EveryByte),
+        ("table", Skip::Table),
+        ("words", InstrTable::new(INSTRUCTIONS).skip),
+    ] {
+        let (total, took) = best_of(|| {
+            let mut scanner = Scanner::new(&input, INSTRUCTIONS);
+            scanner.table.skip = skip.clone();
+            let mut cond = CondBehavior::Enabled(true);
+            return scanner
+                .filter_map(|t| cond.apply(t.instr))
+                .map(|m| m.compute())
+                .sum();
+        });
+        assert_eq!(total, expected, "The scanners disagree");
+        report(name, took);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::BufReader;
+
+    use crate::day3::audit;
+    use crate::day3::corrupted_memory;
+    use crate::day3::highlight;
+    use crate::day3::read_muls;
+    use crate::day3::summary;
+    use crate::day3::CondBehavior;
+    use crate::day3::Instr;
+    use crate::day3::InstrSpec;
+    use crate::day3::InstrTable;
+    use crate::day3::Mul;
+    use crate::day3::Scanner;
+    use crate::day3::Skip;
+    use crate::day3::StreamScanner;
+    use crate::day3::Token;
+    use crate::day3::DO;
+    use crate::day3::INSTRUCTIONS;
+    use crate::day3::MUL;
+
+    const SAMPLE_1: &'static str =
+        "xmul(2,4)%&mul[3,7]!@^do_not_mul(5,5)+mul(32,64]then(mul(11,8)mul(8,5))";
+    const SAMPLE_2: &'static str =
+        "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+
+    fn total(input: &str, cond: CondBehavior) -> u128 {
+        return read_muls(input, cond).map(|m| m.compute()).sum();
+    }
+
+    #[test]
+    fn samples() {
+        assert_eq!(total(SAMPLE_1, CondBehavior::Ignore), 161);
+        assert_eq!(total(SAMPLE_2, CondBehavior::Ignore), 161);
+        assert_eq!(total(SAMPLE_2, CondBehavior::Enabled(true)), 48);
+    }
+
+    #[test]
+    fn tokens_have_spans() {
+        let tokens: Vec<Token> = Scanner::new(SAMPLE_2, INSTRUCTIONS).collect();
+        assert_eq!(tokens.len(), 6);
+        assert_eq!(
+            tokens[1],
+            Token {
+                instr: Instr::Dont,
+                span: 20..27,
+            }
+        );
+        for t in tokens.iter() {
+            let text = &SAMPLE_2[t.span.clone()];
+            match t.instr {
+                Instr::Mul(Mul(a, b)) => assert_eq!(text, format!("mul({},{})", a, b)),
+                Instr::Do => assert_eq!(text, "do()"),
+                Instr::Dont => assert_eq!(text, "don't()"),
+            }
+        }
+    }
+
+    #[test]
+    fn argument_rules() {
+        let muls = |s| read_muls(s, CondBehavior::Ignore).collect::<Vec<Mul>>();
+        assert_eq!(muls("mul(123,4)mul(1234,5)mul(6,7890)"), vec![Mul(123, 4)]);
+        assert_eq!(muls("mul(,1)mul(1,)mul()mul(1 ,2)mul(-1,2)"), vec![]);
+        assert_eq!(muls("mul(1,2,3)mul(4,5"), vec![]);
+        // A failed attempt doesn't hide one starting inside it.
+        assert_eq!(muls("mul(1,mul(2,3))"), vec![Mul(2, 3)]);
+        assert_eq!(muls("mumul(1,2)"), vec![Mul(1, 2)]);
+        assert_eq!(muls("mul(007,08)"), vec![Mul(7, 8)]);
+    }
+
+    #[test]
+    fn conditionals() {
+        let muls = |s| read_muls(s, CondBehavior::Enabled(true)).collect::<Vec<Mul>>();
+        assert_eq!(muls("don'tmul(1,2)"), vec![Mul(1, 2)]);
+        assert_eq!(muls("don't()mul(1,2)do()mul(3,4)"), vec![Mul(3, 4)]);
+        assert_eq!(muls("don't()do(mul(1,2)don't()"), vec![]);
+        assert_eq!(muls("don't()don'do()mul(1,2)"), vec![Mul(1, 2)]);
+    }
+
+    #[test]
+    fn registering_instructions() {
+        // Same instruction, different spelling.
+        let wide_mul = [InstrSpec {
+            name: "mul",
+            arity: 2,
+            max_digits: 5,
+            separator: Some(b';'),
+            build: |args| Instr::Mul(Mul(args[0], args[1])),
+        }];
+        let tokens: Vec<Instr> = Scanner::new("mul(1,2)mul(12345;6)do()", &wide_mul)
+            .map(|t| t.instr)
+            .collect();
+        assert_eq!(tokens, vec![Instr::Mul(Mul(12345, 6))]);
+
+        let only_do = [DO];
+        let tokens: Vec<Token> = Scanner::new("mul(1,2)don't()do()", &only_do).collect();
+        assert_eq!(
+            tokens,
+            vec![Token {
+                instr: Instr::Do,
+                span: 15..19,
+            }]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't register \"\": Empty instruction name")]
+    fn unnamed_instructions_are_refused() {
+        let unnamed = [InstrSpec { name: "", ..DO }];
+        Scanner::new("()", &unnamed);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't register \"mul\": Arguments need a separator")]
+    fn arguments_need_a_separator() {
+        let run_on = [InstrSpec {
+            separator: None,
+            ..MUL
+        }];
+        Scanner::new("mul(12)", &run_on);
+    }
+
+    #[test]
+    fn next_start_by_words() {
+        let table = InstrTable::new(INSTRUCTIONS);
+        assert!(matches!(table.skip, Skip::Words(ref n) if n.len() == 2));
+        // Neighbours of the start bytes, and bytes a borrow could trip over.
+        let junk = b"lnce\x00\xff\x80\x7f\x01(),0 ";
+        for len in 0..20 {
+            let hay: Vec<u8> = (0..len).map(|i| junk[i % junk.len()]).collect();
+            assert_eq!(table.next_start(&hay), None);
+            for at in 0..len {
+                for start in [b'm', b'd'] {
+                    let mut hay = hay.clone();
+                    hay[at] = start;
+                    hay[(at + 3).min(len - 1)] = b'd';
+                    assert_eq!(table.next_start(&hay), Some(at), "{:?}", hay);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn skipping_finds_the_same() {
+        for seed in 1..20 {
+            let input = corrupted_memory(5_000, seed);
+            let mut every_byte = Scanner::new(&input, INSTRUCTIONS);
+            every_byte.table.skip = Skip::EveryByte;
+            let expected: Vec<Token> = every_byte.collect();
+            let mut table = Scanner::new(&input, INSTRUCTIONS);
+            table.table.skip = Skip::Table;
+            assert_eq!(table.collect::<Vec<_>>(), expected);
+            let words = Scanner::new(&input, INSTRUCTIONS);
+            assert!(matches!(words.table.skip, Skip::Words(_)));
+            assert_eq!(words.collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn streaming_across_reads() {
+        let mut inputs = vec![SAMPLE_1.to_string(), SAMPLE_2.to_string()];
+        inputs.extend((1..5).map(|seed| corrupted_memory(3_000, seed)));
+        for input in inputs.iter() {
+            let whole: Vec<Token> = Scanner::new(input, INSTRUCTIONS).collect();
+            // Down to one byte a read, so every instruction gets cut somewhere.
+            for capacity in [1, 2, 3, 7, 12, 64] {
+                let reader = BufReader::with_capacity(capacity, input.as_bytes());
+                let streamed: Vec<Token> = StreamScanner::new(reader, INSTRUCTIONS)
+                    .map(Result::unwrap)
+                    .collect();
+                assert_eq!(streamed, whole, "Capacity {}", capacity);
+            }
+        }
+    }
+
+    #[test]
+    fn streaming_stays_small() {
+        let input = corrupted_memory(100_000, 7);
+        let reader = BufReader::with_capacity(256, input.as_bytes());
+        let mut scanner = StreamScanner::new(reader, INSTRUCTIONS);
+        let mut most = 0;
+        while let Some(t) = scanner.next() {
+            t.unwrap();
+            most = most.max(scanner.buf.len());
+        }
+        assert!(most < 256 + 12, "Buffered {} bytes", most);
+    }
+
+    #[test]
+    fn audit_matches_parts() {
+        let audited = audit(SAMPLE_2);
+        let counted: Vec<Option<bool>> = audited.iter().map(|a| a.counted).collect();
+        assert_eq!(
+            counted,
+            vec![Some(true), None, Some(false), Some(false), None, Some(true)]
+        );
+        let enabled: Vec<bool> = audited.iter().map(|a| a.enabled).collect();
+        assert_eq!(enabled, vec![true, false, false, false, true, true]);
+        assert_eq!(audited[2].token.span, 28..36);
+
+        // Every mul is in part 1, the counted ones are part 2.
+        for input in [SAMPLE_2.to_string(), corrupted_memory(5_000, 3)] {
+            let audited = audit(&input);
+            let counted: Vec<Mul> = audited
+                .iter()
+                .filter_map(|a| match a.token.instr {
+                    Instr::Mul(m) if a.counted == Some(true) => Some(m),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(
+                counted,
+                read_muls(&input, CondBehavior::Enabled(true)).collect::<Vec<Mul>>()
+            );
+        }
+    }
+
+    #[test]
+    fn highlighted_view() {
+        let audited = audit(SAMPLE_2);
+        let view = highlight(SAMPLE_2, &audited);
+        // Only styling is added.
+        let mut plain = view.clone();
+        for code in [
+            "\x1b[1;32m",
+            "\x1b[1;31m",
+            "\x1b[1;36m",
+            "\x1b[2m",
+            "\x1b[0m",
+        ] {
+            plain = plain.replace(code, "");
+        }
+        assert_eq!(plain, SAMPLE_2);
+
+        assert!(view.starts_with("x\x1b[1;32mmul(2,4)\x1b[0m&mul[3,7]"));
+        assert!(view.contains("\x1b[1;36mdon't()\x1b[0m\x1b[2m_\x1b[0m\x1b[1;31mmul(5,5)"));
+        assert!(view.ends_with("\x1b[1;36mdo()\x1b[0m?\x1b[1;32mmul(8,5)\x1b[0m)"));
+    }
+
+    #[test]
+    fn summary_lines() {
+        let audited = audit(SAMPLE_2);
+        let lines: Vec<String> = summary(SAMPLE_2, &audited)
+            .lines()
+            .map(|l| l.to_string())
+            .collect();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(lines[0], "       1 mul(2,4)     counted, 8");
+        assert_eq!(lines[1], "      20 don't()      disables");
+        assert_eq!(lines[2], "      28 mul(5,5)     skipped, disabled");
+        assert_eq!(lines[4], "      59 do()         enables");
+        assert_eq!(lines[6], "4 muls, 2 counted in part 2, 2 skipped");
+    }
+}
+
+#[cfg(test)]
+mod test_reference {
+    use crate::day3::char_search::MulSearcher;
+    use crate::day3::corrupted_memory;
+    use crate::day3::read_muls;
+    use crate::day3::CondBehavior;
+    use crate::day3::Mul;
+    use crate::day3::INPUT_1;
+    use crate::rng::xorshift;
+
+    // 1 to 3 digits and nothing else.
+    fn arg(s: &str) -> Option<u32> {
+        if s.is_empty() || s.len() > 3 || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        return s.parse().ok();
+    }
+
+    // Deliberately nothing like the scanner: find every literal, then check
+    // each `mul(` by splitting on the punctuation it should have.
+    fn reference(input: &str, respect_conds: bool) -> Vec<Mul> {
+        let mut hits: Vec<(usize, &str)> = Vec::new();
+        for lit in ["mul(", "do()", "don't()"] {
+            hits.extend(input.match_indices(lit));
+        }
+        hits.sort();
+
+        let mut out = Vec::new();
+        let mut enabled = true;
+        let mut done_to = 0;
+        for (at, lit) in hits {
+            if at < done_to {
+                continue;
+            }
+            match lit {
+                "do()" => enabled = true,
+                "don't()" => enabled = false,
+                _ => {
+                    let rest = &input[at + 4..];
+                    let Some((args, _)) = rest.split_once(')') else {
+                        continue;
+                    };
+                    let Some((a, b)) = args.split_once(',') else {
+                        continue;
+                    };
+                    let (Some(a), Some(b)) = (arg(a), arg(b)) else {
+                        continue;
+                    };
+                    done_to = at + 4 + args.len() + 1;
+                    if enabled || !respect_conds {
+                        out.push(Mul(a, b));
+                    }
+                },
+            }
+        }
+
+        return out;
+    }
+
+    // Glues together pieces of instructions, so near misses are everywhere.
+    fn fragments(rand: &mut impl FnMut(u32) -> u32) -> String {
+        const PIECES: &'static [&'static str] = &[
+            "mul(", "mul", "mu", "m", "(", ")", ",", "do", "do(", "don", "don'", "don't", "don't(",
+            "()", "d", "x", " ", "ul(",
+        ];
+        let mut out = String::new();
+        for _ in 0..rand(60) {
+            if rand(3) == 0 {
+                // Up to 5 digits, to cross the 3 digit limit.
+                for _ in 0..1 + rand(5) {
+                    out.push((b'0' + rand(10) as u8) as char);
+                }
+            } else {
+                out.push_str(PIECES[rand(PIECES.len() as u32) as usize]);
+            }
+        }
+        return out;
+    }
+
+    fn assert_same(input: &str) {
+        for (cond, respect) in [
+            (CondBehavior::Ignore, false),
+            (CondBehavior::Enabled(true), true),
+        ] {
+            let scanned: Vec<Mul> = read_muls(input, cond).collect();
+            let expected = reference(input, respect);
+            assert_eq!(scanned, expected, "{:?}", input);
+            let total = |muls: &[Mul]| muls.iter().map(|m| m.compute()).sum::<u128>();
+            assert_eq!(total(&scanned), total(&expected));
+        }
+    }
+
+    #[test]
+    fn reference_agrees_on_edge_cases() {
+        for input in [
+            "mul(1,2)",
+            "mul(1234,5)mul(1,2345)mul(123,456)",
+            "mul(mul(1,2))",
+            "mul(1,mul(2,3)",
+            "don'mul(1,2)don't()mul(3,4)don't(do()mul(5,6)",
+            "do(don't()mul(1,2)",
+            "mul(1,2,3)mul(,)mul( 1,2)",
+        ] {
+            assert_same(input);
+        }
+    }
+
+    #[test]
+    fn reference_agrees_on_input() {
+        assert_same(INPUT_1);
+    }
+
+    #[test]
+    fn reference_agrees_on_random_memory() {
+        let mut rand = xorshift(0xD3);
+
+        for _ in 0..20_000 {
+            assert_same(&fragments(&mut rand));
+        }
+        for seed in 1..50 {
+            assert_same(&corrupted_memory(2_000, seed));
+        }
+    }
+
+    // `run_bench` races the old searcher, so it had better find the same.
+    #[test]
+    fn old_searcher_agrees() {
+        let mut inputs = vec![INPUT_1.to_string()];
+        inputs.extend((1..20).map(|seed| corrupted_memory(5_000, seed)));
+        for input in inputs.iter() {
+            let old: Vec<Mul> =
+                MulSearcher::new(input.chars(), CondBehavior::Enabled(true)).collect();
+            assert_eq!(
+                old,
+                read_muls(input, CondBehavior::Enabled(true)).collect::<Vec<Mul>>()
+            );
+        }
     }
-    println!("Day 3, Part 2: {}", total);
 }
@@ -0,0 +1,160 @@
+// The day 3 searcher from before the byte scanner: one `char` at a time off a
+// `Peekable`, with a `String` per number. It's the baseline `run_bench` has
+// to beat, so leave it as it was.
+use std::iter::Peekable;
+
+use crate::day3::CondBehavior;
+use crate::day3::Mul;
+
+pub(super) struct MulSearcher<It>
+where
+    It: Iterator,
+{
+    inner_iter: Peekable<It>,
+    cond: CondBehavior,
+}
+
+impl<T> MulSearcher<T>
+where
+    T: Iterator,
+{
+    pub(super) fn new(i: T, cond: CondBehavior) -> MulSearcher<T> {
+        MulSearcher {
+            inner_iter: i.peekable(),
+            cond,
+        }
+    }
+}
+
+fn take_through<I>(char_iter: &mut Peekable<I>, nxt: &[char]) -> bool
+where
+    I: Iterator<Item = char>,
+{
+    for expected in nxt.iter() {
+        if let Some(actual) = char_iter.peek() {
+            if *actual == *expected {
+                // Actually eat this one.
+                char_iter.next();
+            } else {
+                return false;
+            }
+        } else {
+            // Reached the end.
+            return false;
+        }
+    }
+
+    return true;
+}
+
+// Returns None if the next character is not a digit, or if the number
+// of sequential digits is > 3
+fn read_digit_until<I>(char_iter: &mut Peekable<I>, marker: char) -> Option<u32>
+where
+    I: Iterator<Item = char>,
+{
+    let mut acc = String::new();
+    loop {
+        match char_iter.peek() {
+            Some(d) if d.is_ascii_digit() => {
+                // Eat the digit
+                acc.extend(std::iter::once(d));
+            },
+            Some(c) if *c == marker => {
+                break;
+            },
+            // Either it wasn't a digit, or we reached the end.
+            Some(_) | None => {
+                return None;
+            },
+        }
+        char_iter.next();
+    }
+
+    // Getting here means we found the terminal marker, but we still need to eat it.
+    // Can't .next inside match bc of borrow for peek()
+    char_iter.next();
+    if acc.len() > 3 {
+        return None;
+    } else {
+        return acc.parse().ok();
+    }
+}
+
+impl<T> Iterator for MulSearcher<T>
+where
+    T: Iterator<Item = char>,
+{
+    type Item = Mul;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner_iter.next() {
+                Some('m') => {
+                    if !take_through(&mut self.inner_iter, &['u', 'l', '(']) {
+                        continue;
+                    }
+
+                    // Now we parse digits ...
+                    let left = match read_digit_until(&mut self.inner_iter, ',') {
+                        Some(n) => n,
+                        None => {
+                            continue;
+                        },
+                    };
+
+                    let right = match read_digit_until(&mut self.inner_iter, ')') {
+                        Some(n) => n,
+                        None => {
+                            continue;
+                        },
+                    };
+
+                    // Skip while not included.
+                    if self.cond.should_include() {
+                        return Some(Mul(left, right));
+                    }
+                },
+                Some('d') => {
+                    if !take_through(&mut self.inner_iter, &['o']) {
+                        continue;
+                    }
+
+                    let nxt_peek = if let Some(c) = self.inner_iter.peek() {
+                        *c
+                    } else {
+                        continue;
+                    };
+                    match nxt_peek {
+                        'n' => {
+                            // Take the char
+                            self.inner_iter.next();
+                            if !take_through(&mut self.inner_iter, &['\'', 't', '(', ')']) {
+                                continue;
+                            }
+                            self.cond.set(false);
+                        },
+                        '(' => {
+                            // Take the char
+                            self.inner_iter.next();
+                            if !take_through(&mut self.inner_iter, &[')']) {
+                                continue;
+                            }
+                            self.cond.set(true);
+                        },
+                        _ => {
+                            continue;
+                        },
+                    }
+                },
+                // We're just looking for 'm' to start a mul
+                Some(_) => {
+                    continue;
+                },
+                None => {
+                    return None;
+                },
+            }
+        }
+    }
+}
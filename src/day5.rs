@@ -1,90 +1,503 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::fmt::Display;
+use std::fmt::Write;
 use std::str::FromStr;
 
+use crate::answer::Answer;
+use crate::digraph::DiGraph;
+use crate::parse::ParseError;
+use crate::tally::Overflow;
+use crate::tally::Tally;
+use crate::tally::Wide;
+
 const INPUT_1: &'static str = include_str!("day5-1.txt");
 
 struct OrderRules {
-    // Maps a particular page to all pages that must come before that one.
-    rule_map: HashMap<u32, HashSet<u32>>,
-    forward_rule_map: HashMap<u32, HashSet<u32>>,
+    // An edge for each `before|after` rule.
+    graph: DiGraph<u32>,
 }
 
 impl OrderRules {
     fn new() -> OrderRules {
         return OrderRules {
-            rule_map: HashMap::new(),
-            forward_rule_map: HashMap::new(),
+            graph: DiGraph::new(),
         };
     }
 
-    fn topo(&self, pu: &PageUpdate) -> Option<PageUpdate> {
-        if self.validate_update(pu) {
-            return None;
+    // Just the rules between pages of this update, which also covers pages no
+    // rule mentions.
+    fn restricted_to(&self, pu: &PageUpdate) -> DiGraph<u32> {
+        return self.graph.induced(pu.0.iter().copied());
+    }
+
+    // Kahn's algorithm over just the rules between pages of this update.
+    // Whenever several pages are free to go next, the earliest in `pu` wins,
+    // so an already valid update comes back unchanged.
+    // Pages no rule mentions are unconstrained, and repeated pages stay together
+    // where their first copy lands.
+    fn topo(&self, pu: &PageUpdate) -> Result<PageUpdate, OrderCycle> {
+        return self.topo_with(pu, &HashMap::new());
+    }
+
+    // `extra` adds a `before -> after` edge on top of the rules for each entry.
+    fn topo_with(
+        &self,
+        pu: &PageUpdate,
+        extra: &HashMap<u32, u32>,
+    ) -> Result<PageUpdate, OrderCycle> {
+        let position: HashMap<u32, usize> = pu.iter().rev().map(|(idx, p)| (*p, idx)).collect();
+        let mut copies: HashMap<u32, usize> = HashMap::new();
+        for (_, p) in pu.iter() {
+            *copies.entry(*p).or_insert(0) += 1;
         }
 
-        let mut upd2 = pu.clone();
-        while !self.validate_update(&upd2) {
-            let mut seen: HashSet<u32> = HashSet::with_capacity(upd2.0.len());
-            for idx in 0..upd2.0.len() {
-                let dep_list = self.forward_rule_map.get(&upd2.0[idx]).unwrap();
-                // Have we seen one's that I must be placed before?
-                if !seen.is_disjoint(dep_list) {
-                    // Find first one ...
-                    let must_be_before = upd2.0.iter().position(|x| dep_list.contains(x)).unwrap();
-                    let old = upd2.0.remove(idx);
-                    upd2.0.insert(must_be_before, old);
-                    break;
-                } else {
-                    seen.insert(upd2.0[idx]);
-                }
-            }
+        let mut g = self.restricted_to(pu);
+        for (before, after) in extra.iter() {
+            g.add_edge(*before, *after);
         }
 
-        return Some(upd2);
+        let sorted = g.topo_order_by(|p| position[p]).map_err(OrderCycle::new)?;
+        return Ok(PageUpdate(
+            sorted
+                .into_iter()
+                .flat_map(|p| std::iter::repeat_n(p, copies[&p]))
+                .collect(),
+        ));
     }
 
     fn add_rule(&mut self, before: u32, after: u32) {
-        if let Some(pages) = self.rule_map.get_mut(&after) {
-            pages.insert(before);
-        } else {
-            let mut h = HashSet::new();
-            h.insert(before);
-            self.rule_map.insert(after, h);
-        }
-
-        if let Some(pages) = self.forward_rule_map.get_mut(&before) {
-            pages.insert(after);
-        } else {
-            let mut h = HashSet::new();
-            h.insert(after);
-            self.forward_rule_map.insert(before, h);
-        }
+        self.graph.add_edge(before, after);
     }
 
     fn validate_update(&self, upd: &PageUpdate) -> bool {
         let mut seen: HashSet<u32> = HashSet::new();
         for (_, nxt) in upd.iter().rev() {
-            if let Some(found_illegal) = self
-                .rule_map
-                .get(&nxt)
-                .map(|pages| pages.iter().any(|only_before| seen.contains(only_before)))
+            if self
+                .graph
+                .predecessors(nxt)
+                .any(|only_before| seen.contains(only_before))
             {
-                if found_illegal {
-                    return false;
-                }
+                return false;
             }
             seen.insert(*nxt);
         }
         return true;
     }
+
+    // Every pair of pages that breaks a rule, in update order.
+    fn violations(&self, upd: &PageUpdate) -> Vec<Violation> {
+        let mut out = Vec::new();
+        for (after_idx, after) in upd.iter() {
+            for (before_idx, before) in upd.iter().skip(after_idx + 1) {
+                if self.graph.has_edge(before, after) {
+                    out.push(Violation {
+                        before: *before,
+                        after: *after,
+                        before_idx,
+                        after_idx,
+                    });
+                }
+            }
+        }
+        return out;
+    }
+
+    // The fewest pages to pick up and put back elsewhere to make `upd` valid.
+    // Applied in order, each move keeps the ones before it in place.
+    fn fewest_moves(&self, upd: &PageUpdate) -> Result<Vec<Move>, OrderCycle> {
+        // Bail on cycles first, everything below assumes the rules are a DAG.
+        self.topo(upd)?;
+
+        // `inverted(i, j)`: page i is earlier but page j must come before it.
+        // That's a partial order, and the pages we can leave alone are exactly
+        // an antichain of it, so the biggest one comes out of Dilworth/König
+        // with a bipartite matching.
+        let n = upd.0.len();
+        let reach = self.restricted_to(upd).transitive_closure();
+        let inverted = |i: usize, j: usize| i < j && reach.has_edge(&upd.0[j], &upd.0[i]);
+
+        let mut match_of_right: Vec<Option<usize>> = vec![None; n];
+        for i in 0..n {
+            augment(i, n, &inverted, &mut match_of_right, &mut vec![false; n]);
+        }
+        let mut match_of_left: Vec<Option<usize>> = vec![None; n];
+        for (j, i) in match_of_right.iter().enumerate() {
+            if let Some(i) = i {
+                match_of_left[*i] = Some(j);
+            }
+        }
+
+        // Alternating paths from unmatched left vertices. The min vertex cover
+        // is the left side they miss plus the right side they hit.
+        let mut z_left = vec![false; n];
+        let mut z_right = vec![false; n];
+        let mut stack: Vec<usize> = (0..n).filter(|i| match_of_left[*i].is_none()).collect();
+        for i in stack.iter() {
+            z_left[*i] = true;
+        }
+        while let Some(i) = stack.pop() {
+            for j in 0..n {
+                if inverted(i, j) && !z_right[j] && match_of_left[i] != Some(j) {
+                    z_right[j] = true;
+                    if let Some(i2) = match_of_right[j] {
+                        if !z_left[i2] {
+                            z_left[i2] = true;
+                            stack.push(i2);
+                        }
+                    }
+                }
+            }
+        }
+        let keep: Vec<u32> = (0..n)
+            .filter(|i| z_left[*i] && !z_right[*i])
+            .map(|i| upd.0[i])
+            .collect();
+
+        // Chaining the kept pages on top of the rules gives an order that leaves them be.
+        let chain: HashMap<u32, u32> = keep
+            .windows(2)
+            .filter(|w| w[0] != w[1])
+            .map(|w| (w[0], w[1]))
+            .collect();
+        let fixed = self.topo_with(upd, &chain)?;
+
+        let mut kept_copies: HashMap<u32, usize> = HashMap::new();
+        for p in keep.iter() {
+            *kept_copies.entry(*p).or_insert(0) += 1;
+        }
+        let mut moves = Vec::new();
+        for (idx, p) in fixed.iter() {
+            match kept_copies.get_mut(p) {
+                Some(cnt) if *cnt > 0 => *cnt -= 1,
+                _ => moves.push(Move {
+                    page: *p,
+                    after: idx.checked_sub(1).map(|prev| fixed.0[prev]),
+                }),
+            }
+        }
+        return Ok(moves);
+    }
+
+    // How much freedom the rules leave for ordering this update's pages.
+    fn analyze_ordering(&self, upd: &PageUpdate) -> Result<OrderingAnalysis, OrderCycle> {
+        let g = self.restricted_to(upd);
+        let topo = g.topo_order().map_err(OrderCycle::new)?;
+        // Only one order if every neighbour in it is forced by a rule.
+        let unique = topo.windows(2).all(|w| g.has_edge(&w[0], &w[1]));
+        let closure = g.transitive_closure();
+
+        let mut pages: Vec<u32> = g.nodes().copied().collect();
+        pages.sort();
+
+        let mut unconstrained = Vec::new();
+        for (idx, a) in pages.iter().enumerate() {
+            for b in pages[idx + 1..].iter() {
+                if !closure.has_edge(a, b) && !closure.has_edge(b, a) {
+                    unconstrained.push((*a, *b));
+                }
+            }
+        }
+
+        // A page can land anywhere between having all its must-come-befores
+        // ahead of it and all its must-come-afters behind it.
+        let mid = pages.len() / 2;
+        let possible_midpoints = pages
+            .iter()
+            .filter(|p| {
+                let before = closure.predecessors(p).count();
+                let after = closure.successors(p).count();
+                return before <= mid && mid + after < pages.len();
+            })
+            .copied()
+            .collect();
+
+        return Ok(OrderingAnalysis {
+            count: count_orderings(&g, &pages),
+            unique,
+            unconstrained,
+            possible_midpoints,
+        });
+    }
+
+    // Graphviz source for the rules, `before -> after`. Given an update, only
+    // its pages are drawn, labelled with their index, and broken rules are red.
+    fn to_dot(&self, only: Option<&PageUpdate>) -> String {
+        let mut edges: Vec<(u32, u32)> = self.graph.edges().map(|(b, a)| (*b, *a)).collect();
+        edges.sort();
+
+        let mut out = String::new();
+        let mut broken = HashSet::new();
+        match only {
+            None => {
+                writeln!(out, "digraph rules {{").unwrap();
+            },
+            Some(upd) => {
+                writeln!(out, "digraph update {{").unwrap();
+                writeln!(out, "    rankdir=LR;").unwrap();
+                for (idx, p) in upd.iter() {
+                    writeln!(out, "    {} [label=\"{} @{}\"];", p, p, idx).unwrap();
+                }
+                let pages: HashSet<u32> = upd.0.iter().copied().collect();
+                edges.retain(|(before, after)| pages.contains(before) && pages.contains(after));
+                broken.extend(self.violations(upd).iter().map(|v| (v.before, v.after)));
+            },
+        }
+
+        for (before, after) in edges.iter() {
+            if broken.contains(&(*before, *after)) {
+                writeln!(out, "    {} -> {} [color=red];", before, after).unwrap();
+            } else {
+                writeln!(out, "    {} -> {};", before, after).unwrap();
+            }
+        }
+        out.push_str("}\n");
+        return out;
+    }
+}
+
+// Past this, counting orderings one by one isn't worth it.
+const ORDERING_CAP: u128 = 100_000;
+
+// How many valid orderings an update has, as far as it was worth counting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum OrderingCount {
+    Exactly(u128),
+    AtLeast(u128),
+}
+
+impl Display for OrderingCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            OrderingCount::Exactly(n) => write!(f, "{}", n),
+            OrderingCount::AtLeast(n) => write!(f, "at least {}", n),
+        };
+    }
+}
+
+// Pages in different connected pieces of the rules interleave freely, so
+// each piece is counted on its own and the pieces are shuffled together,
+// `n! / (k1! k2! ...)` ways. Within a piece, orderings are counted one at a
+// time up to `ORDERING_CAP`. Anything too big for a u128 is a lower bound.
+fn count_orderings(g: &DiGraph<u32>, pages: &[u32]) -> OrderingCount {
+    let mut seen = HashSet::new();
+    let mut exact = true;
+    let mut total: u128 = 1;
+    let mut placed = 0;
+    for start in pages {
+        if !seen.insert(*start) {
+            continue;
+        }
+
+        let mut piece = vec![*start];
+        let mut todo = vec![*start];
+        while let Some(p) = todo.pop() {
+            for q in g.successors(&p).chain(g.predecessors(&p)) {
+                if seen.insert(*q) {
+                    piece.push(*q);
+                    todo.push(*q);
+                }
+            }
+        }
+
+        placed += piece.len();
+        let (ways, complete) = count_piece(g, &piece);
+        exact &= complete;
+        let step = binomial(placed as u128, piece.len() as u128).and_then(|c| c.checked_mul(ways));
+        match step.and_then(|n| total.checked_mul(n)) {
+            Some(n) => total = n,
+            None => return OrderingCount::AtLeast(u128::MAX),
+        }
+    }
+
+    return if exact {
+        OrderingCount::Exactly(total)
+    } else {
+        OrderingCount::AtLeast(total)
+    };
+}
+
+// Orderings of one connected piece, and whether that's all of them. Every
+// partial ordering can be finished, so this stops after about
+// `ORDERING_CAP * piece.len()` steps however the rules look.
+fn count_piece(g: &DiGraph<u32>, piece: &[u32]) -> (u128, bool) {
+    let idx: HashMap<u32, usize> = piece.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+    let succ: Vec<Vec<usize>> = piece
+        .iter()
+        .map(|p| g.successors(p).map(|q| idx[q]).collect())
+        .collect();
+    let mut waiting_on: Vec<usize> = piece.iter().map(|p| g.predecessors(p).count()).collect();
+    let mut placed = vec![false; piece.len()];
+
+    let mut found = 0;
+    extend_orderings(&succ, &mut waiting_on, &mut placed, piece.len(), &mut found);
+    return (found, found < ORDERING_CAP);
+}
+
+fn extend_orderings(
+    succ: &[Vec<usize>],
+    waiting_on: &mut [usize],
+    placed: &mut [bool],
+    left: usize,
+    found: &mut u128,
+) {
+    if left == 0 {
+        *found += 1;
+        return;
+    }
+
+    for i in 0..succ.len() {
+        if placed[i] || waiting_on[i] > 0 {
+            continue;
+        }
+
+        placed[i] = true;
+        for s in succ[i].iter() {
+            waiting_on[*s] -= 1;
+        }
+        extend_orderings(succ, waiting_on, placed, left - 1, found);
+        for s in succ[i].iter() {
+            waiting_on[*s] += 1;
+        }
+        placed[i] = false;
+
+        if *found >= ORDERING_CAP {
+            return;
+        }
+    }
+}
+
+// `n` choose `k`, if it fits.
+fn binomial(n: u128, k: u128) -> Option<u128> {
+    let k = k.min(n - k);
+    let mut out: u128 = 1;
+    for i in 0..k {
+        // out * (n - i) / (i + 1) is the next binomial, so divides exactly.
+        // Dividing first keeps the product no bigger than the answer.
+        let common = gcd(out, i + 1);
+        out = (out / common).checked_mul((n - i) / ((i + 1) / common))?;
+    }
+    return Some(out);
+}
+
+fn gcd(a: u128, b: u128) -> u128 {
+    return if b == 0 { a } else { gcd(b, a % b) };
+}
+
+// Kuhn's augmenting path step for `fewest_moves`.
+fn augment(
+    i: usize,
+    n: usize,
+    edge: &impl Fn(usize, usize) -> bool,
+    match_of_right: &mut [Option<usize>],
+    seen: &mut [bool],
+) -> bool {
+    for j in 0..n {
+        if edge(i, j) && !seen[j] {
+            seen[j] = true;
+            let free = match match_of_right[j] {
+                None => true,
+                Some(i2) => augment(i2, n, edge, match_of_right, seen),
+            };
+            if free {
+                match_of_right[j] = Some(i);
+                return true;
+            }
+        }
+    }
+    return false;
+}
+
+// Rule `before|after` broken by `after` showing up first.
+#[derive(Debug, PartialEq, Eq)]
+struct Violation {
+    before: u32,
+    after: u32,
+    before_idx: usize,
+    after_idx: usize,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(
+            f,
+            "{}|{}: {} at {} is after {} at {}",
+            self.before, self.after, self.before, self.before_idx, self.after, self.after_idx
+        );
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct OrderingAnalysis {
+    // Valid orderings of the update's pages.
+    count: OrderingCount,
+    // Whether the rules force a single ordering.
+    unique: bool,
+    // Pairs (lower page first) no rule orders, even indirectly.
+    unconstrained: Vec<(u32, u32)>,
+    // Pages that sit in the middle of at least one valid ordering.
+    possible_midpoints: Vec<u32>,
+}
+
+impl OrderingAnalysis {
+    fn is_unique(&self) -> bool {
+        return self.unique;
+    }
+
+    fn midpoint_is_defined(&self) -> bool {
+        return self.possible_midpoints.len() == 1;
+    }
+}
+
+// Take `page` out and put it back right after `after`, or at the front.
+#[derive(Debug, PartialEq, Eq)]
+struct Move {
+    page: u32,
+    after: Option<u32>,
+}
+
+impl Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self.after {
+            Some(after) => write!(f, "move {} after {}", self.page, after),
+            None => write!(f, "move {} to the front", self.page),
+        };
+    }
+}
+
+// Pages whose rules require each to come before the next, and the last before the first.
+#[derive(Debug, PartialEq, Eq)]
+struct OrderCycle(Vec<u32>);
+
+impl OrderCycle {
+    // Starting from the lowest page, so the same cycle always reads the same.
+    fn new(mut pages: Vec<u32>) -> OrderCycle {
+        let lowest = (0..pages.len()).min_by_key(|idx| pages[*idx]).unwrap();
+        pages.rotate_left(lowest);
+        return OrderCycle(pages);
+    }
+}
+
+impl Display for OrderCycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Rules form a cycle: ")?;
+        for p in self.0.iter() {
+            write!(f, "{} -> ", p)?;
+        }
+        return write!(f, "{}", self.0[0]);
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct PageUpdate(Vec<u32>);
 
 impl PageUpdate {
     fn midpoint(&self) -> u32 {
+        // Parsing already rejects these, this is for hand-built ones.
+        assert!(
+            self.0.len() % 2 == 1,
+            "No midpoint in an even-length update"
+        );
         return self.0[self.0.len() / 2];
     }
 
@@ -93,73 +506,608 @@ impl PageUpdate {
     }
 }
 
+impl Display for PageUpdate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (idx, p) in self.iter() {
+            if idx > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", p)?;
+        }
+        return Ok(());
+    }
+}
+
 impl FromStr for PageUpdate {
-    type Err = &'static str;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(ParseError::at_col(1, "Empty update"));
+        }
+
         let mut u = PageUpdate(Vec::new());
+        let mut seen = HashSet::new();
+        let mut col = 1;
         for n in s.split(',') {
-            u.0.push(n.parse().map_err(|_| "A number")?);
+            if n.is_empty() {
+                return Err(if col > s.len() {
+                    ParseError::at_col(col - 1, "Trailing comma")
+                } else {
+                    ParseError::at_col(col, "Missing page")
+                });
+            }
+
+            let page = n
+                .parse()
+                .map_err(|_| ParseError::at_col(col, "Not a page number"))?;
+            if !seen.insert(page) {
+                return Err(ParseError::at_col(col, "Duplicate page"));
+            }
+            u.0.push(page);
+            col += n.len() + 1;
+        }
+
+        if u.0.len().is_multiple_of(2) {
+            return Err(ParseError::at_col(1, "Even number of pages, no midpoint"));
         }
 
         return Ok(u);
     }
 }
 
-fn parse_input() -> Result<(OrderRules, Vec<PageUpdate>), &'static str> {
+fn parse_rule(l: &str) -> Result<(u32, u32), ParseError> {
+    let Some((before, after)) = l.split_once('|') else {
+        return Err(ParseError::at_col(1, "Not a before|after rule"));
+    };
+    let before = before
+        .parse()
+        .map_err(|_| ParseError::at_col(1, "Before bad"))?;
+    let after_col = l.find('|').unwrap() + 2;
+    let after = after
+        .parse()
+        .map_err(|_| ParseError::at_col(after_col, "After bad"))?;
+    return Ok((before, after));
+}
+
+fn parse_input(s: &str) -> Result<(OrderRules, Vec<PageUpdate>), ParseError> {
     let mut order = OrderRules::new();
     let mut updates = Vec::new();
     let mut passed_rules = false;
-    for l in INPUT_1.lines() {
+    for (idx, l) in s.lines().enumerate() {
+        let on_line = |e: ParseError| e.on_line(idx + 1);
+
         // Empty line indicates swap to update reading mode.
-        if l == "" {
+        if l.is_empty() && !passed_rules {
             passed_rules = true;
             continue;
         }
 
         if !passed_rules {
-            let (before, after) = {
-                let mut i = l.split('|');
-                (
-                    i.next()
-                        .ok_or("No before")?
-                        .parse()
-                        .map_err(|_| "Before bad")?,
-                    i.next()
-                        .ok_or("No after")?
-                        .parse()
-                        .map_err(|_| "After bad")?,
-                )
-            };
+            let (before, after) = parse_rule(l).map_err(on_line)?;
             order.add_rule(before, after);
         } else {
-            updates.push(l.parse()?);
+            updates.push(l.parse().map_err(on_line)?);
         }
     }
 
     return Ok((order, updates));
 }
 
-pub fn run_p1() {
-    let mut total = 0;
-    let (order, updates) = parse_input().expect("It to parse");
+fn read_input() -> (OrderRules, Vec<PageUpdate>) {
+    return parse_input(INPUT_1).unwrap_or_else(|e| panic!("Day 5 input, {}", e));
+}
+
+fn sum_valid_midpoints(order: &OrderRules, updates: &[PageUpdate]) -> Result<Wide, Overflow> {
+    let mut total = Tally::new(5, 1);
     for upd in updates.iter() {
-        if let None = order.topo(upd) {
-            total += upd.midpoint();
+        if order.validate_update(upd) {
+            total.add(upd.midpoint())?;
         }
     }
+    return Ok(total.total());
+}
 
-    println!("Day 5, Part 1: {}", total);
+pub fn run_p1() -> Answer {
+    let (order, updates) = read_input();
+    return sum_valid_midpoints(&order, &updates)
+        .unwrap_or_else(|e| panic!("{}", e))
+        .into();
 }
 
-pub fn run_p2() {
-    let mut total = 0;
-    let (order, updates) = parse_input().expect("It to parse");
+fn sum_fixed_midpoints(order: &OrderRules, updates: &[PageUpdate]) -> Result<Wide, Overflow> {
+    let mut total = Tally::new(5, 2);
     for upd in updates.iter() {
-        if let Some(new_order) = order.topo(upd) {
-            total += new_order.midpoint();
+        if !order.validate_update(upd) {
+            let new_order = order.topo(upd).unwrap_or_else(|e| panic!("{}", e));
+            total.add(new_order.midpoint())?;
         }
     }
+    return Ok(total.total());
+}
+
+pub fn run_p2() -> Answer {
+    let (order, updates) = read_input();
+    return sum_fixed_midpoints(&order, &updates)
+        .unwrap_or_else(|e| panic!("{}", e))
+        .into();
+}
+
+// Why each invalid update fails, and the fewest moves that fix it.
+pub fn run_explain() {
+    let (order, updates) = read_input();
+    for (line, upd) in updates.iter().enumerate() {
+        let violations = order.violations(upd);
+        if violations.is_empty() {
+            continue;
+        }
+
+        println!(
+            "Update {} ({}) breaks {} rule(s):",
+            line + 1,
+            upd,
+            violations.len()
+        );
+        for v in violations.iter() {
+            println!("  {}", v);
+        }
+        match order.fewest_moves(upd) {
+            Ok(moves) => {
+                println!("  Fixed in {} move(s):", moves.len());
+                for m in moves.iter() {
+                    println!("    {}", m);
+                }
+            },
+            Err(e) => println!("  Can't be fixed. {}", e),
+        }
+    }
+}
+
+// Whether every update has exactly one valid order, and so a well-defined midpoint.
+pub fn run_orderings() {
+    let (order, updates) = read_input();
+    let mut ambiguous = 0;
+    for (line, upd) in updates.iter().enumerate() {
+        let analysis = match order.analyze_ordering(upd) {
+            Ok(a) => a,
+            Err(e) => {
+                println!("Update {} ({}): {}", line + 1, upd, e);
+                ambiguous += 1;
+                continue;
+            },
+        };
+        if analysis.is_unique() {
+            continue;
+        }
+
+        ambiguous += 1;
+        println!(
+            "Update {} ({}) has {} valid orderings",
+            line + 1,
+            upd,
+            analysis.count
+        );
+        let pairs: Vec<String> = analysis
+            .unconstrained
+            .iter()
+            .map(|(a, b)| format!("{}/{}", a, b))
+            .collect();
+        println!("  Unconstrained: {}", pairs.join(" "));
+        if analysis.midpoint_is_defined() {
+            println!("  Midpoint is always {}", analysis.possible_midpoints[0]);
+        } else {
+            println!(
+                "  Midpoint could be any of {:?}",
+                analysis.possible_midpoints
+            );
+        }
+    }
+    println!(
+        "{} of {} updates have a unique order",
+        updates.len() - ambiguous,
+        updates.len()
+    );
+}
+
+// All the rules as DOT, or just those between the pages of the given
+// (1-based) update.
+pub fn run_dot(update: Option<usize>) {
+    let (order, updates) = read_input();
+    let only = update.map(|n| {
+        return n
+            .checked_sub(1)
+            .and_then(|idx| updates.get(idx))
+            .unwrap_or_else(|| panic!("No update {}, there are {}", n, updates.len()));
+    });
+    print!("{}", order.to_dot(only));
+}
+
+#[cfg(test)]
+mod test {
+    use crate::day5::binomial;
+    use crate::day5::parse_input;
+    use crate::day5::Move;
+    use crate::day5::OrderCycle;
+    use crate::day5::OrderRules;
+    use crate::day5::OrderingCount;
+    use crate::day5::PageUpdate;
+    use crate::day5::Violation;
+    use crate::day5::ORDERING_CAP;
+    use crate::parse::ParseError;
+
+    const SAMPLE: &'static str = "\
+47|53
+97|13
+97|61
+97|47
+75|29
+61|13
+75|53
+29|13
+97|29
+53|29
+61|53
+97|53
+61|29
+47|13
+75|47
+97|75
+47|61
+75|61
+47|29
+75|13
+53|13
+
+75,47,61,53,29
+97,61,53,29,13
+75,29,13
+75,97,47,61,53
+61,13,29
+97,13,75,29,47";
+
+    fn update(pages: &[u32]) -> PageUpdate {
+        return PageUpdate(pages.to_vec());
+    }
+
+    #[test]
+    fn sample_corrections() {
+        let (order, updates) = parse_input(SAMPLE).unwrap();
+        let fixed: Vec<PageUpdate> = updates.iter().map(|u| order.topo(u).unwrap()).collect();
+        assert_eq!(
+            fixed,
+            vec![
+                update(&[75, 47, 61, 53, 29]),
+                update(&[97, 61, 53, 29, 13]),
+                update(&[75, 29, 13]),
+                update(&[97, 75, 47, 61, 53]),
+                update(&[61, 29, 13]),
+                update(&[97, 75, 47, 29, 13]),
+            ]
+        );
+        assert!(fixed.iter().all(|u| order.validate_update(u)));
+    }
+
+    #[test]
+    fn valid_update_is_unchanged() {
+        let (order, _) = parse_input(SAMPLE).unwrap();
+        let u = update(&[97, 61, 53, 29, 13]);
+        assert_eq!(order.topo(&u), Ok(u));
+    }
 
-    println!("Day 5, Part 2: {}", total);
+    #[test]
+    fn pages_without_rules_are_unconstrained() {
+        let (order, _) = parse_input(SAMPLE).unwrap();
+        // 13 is never on the left of a rule, 5 and 6 are in none at all.
+        assert_eq!(order.topo(&update(&[13, 5, 47])), Ok(update(&[5, 47, 13])));
+        assert_eq!(order.topo(&update(&[6, 5])), Ok(update(&[6, 5])));
+        assert!(order.validate_update(&update(&[13, 5])));
+    }
+
+    #[test]
+    fn duplicate_pages_are_kept() {
+        let (order, _) = parse_input(SAMPLE).unwrap();
+        assert_eq!(
+            order.topo(&update(&[53, 47, 53, 75])),
+            Ok(update(&[75, 47, 53, 53]))
+        );
+        assert_eq!(order.topo(&update(&[5, 5])), Ok(update(&[5, 5])));
+    }
+
+    #[test]
+    fn single_page_update() {
+        let (order, _) = parse_input(SAMPLE).unwrap();
+        assert_eq!(order.topo(&update(&[13])), Ok(update(&[13])));
+        assert_eq!(order.topo(&update(&[7])), Ok(update(&[7])));
+        assert!(order.validate_update(&update(&[7])));
+    }
+
+    #[test]
+    fn cycle_is_reported() {
+        let mut order = OrderRules::new();
+        order.add_rule(1, 2);
+        order.add_rule(2, 3);
+        order.add_rule(3, 1);
+        order.add_rule(3, 4);
+        order.add_rule(5, 1);
+        let err = order.topo(&update(&[4, 3, 5, 2, 1])).unwrap_err();
+        assert_eq!(err, OrderCycle(vec![1, 2, 3]));
+        assert_eq!(err.to_string(), "Rules form a cycle: 1 -> 2 -> 3 -> 1");
+    }
+
+    #[test]
+    fn cycle_outside_update_is_ignored() {
+        let mut order = OrderRules::new();
+        order.add_rule(1, 2);
+        order.add_rule(2, 3);
+        order.add_rule(3, 1);
+        assert_eq!(order.topo(&update(&[2, 1])), Ok(update(&[1, 2])));
+    }
+
+    #[test]
+    fn violations_name_rules_and_indices() {
+        let (order, _) = parse_input(SAMPLE).unwrap();
+        assert!(order.violations(&update(&[75, 47, 61, 53, 29])).is_empty());
+        assert_eq!(
+            order.violations(&update(&[61, 13, 29])),
+            vec![Violation {
+                before: 29,
+                after: 13,
+                before_idx: 2,
+                after_idx: 1,
+            }]
+        );
+        let v = order.violations(&update(&[97, 13, 75, 29, 47]));
+        assert_eq!(v.len(), 4);
+        assert_eq!(v[0].to_string(), "75|13: 75 at 2 is after 13 at 1");
+    }
+
+    #[test]
+    fn fewest_moves_on_sample() {
+        let (order, updates) = parse_input(SAMPLE).unwrap();
+        let counts: Vec<usize> = updates
+            .iter()
+            .map(|u| order.fewest_moves(u).unwrap().len())
+            .collect();
+        // The last one keeps 97, 75 and one of 29 or 47 in place.
+        assert_eq!(counts, vec![0, 0, 0, 1, 1, 2]);
+        assert_eq!(
+            order.fewest_moves(&update(&[61, 13, 29])),
+            Ok(vec![Move {
+                page: 13,
+                after: Some(29),
+            }])
+        );
+    }
+
+    #[test]
+    fn fewest_moves_beats_kahn() {
+        // Kahn's ordering for this is 4,1,2,3: two moves. Moving 1 alone is enough.
+        let mut order = OrderRules::new();
+        order.add_rule(1, 2);
+        order.add_rule(1, 3);
+        assert_eq!(
+            order.fewest_moves(&update(&[2, 3, 4, 1])),
+            Ok(vec![Move {
+                page: 1,
+                after: None,
+            }])
+        );
+    }
+
+    #[test]
+    fn applying_moves_fixes_update() {
+        let (order, updates) = parse_input(SAMPLE).unwrap();
+        for upd in updates.iter() {
+            let mut pages = upd.0.clone();
+            for m in order.fewest_moves(upd).unwrap() {
+                pages.retain(|p| *p != m.page);
+                let at = m
+                    .after
+                    .map_or(0, |a| pages.iter().position(|p| *p == a).unwrap() + 1);
+                pages.insert(at, m.page);
+            }
+            assert!(order.validate_update(&PageUpdate(pages)));
+        }
+    }
+
+    #[test]
+    fn dot_for_all_rules() {
+        let mut order = OrderRules::new();
+        order.add_rule(2, 1);
+        order.add_rule(1, 3);
+        assert_eq!(
+            order.to_dot(None),
+            "digraph rules {\n    1 -> 3;\n    2 -> 1;\n}\n"
+        );
+    }
+
+    #[test]
+    fn dot_for_update_highlights_violations() {
+        let (order, _) = parse_input(SAMPLE).unwrap();
+        assert_eq!(
+            order.to_dot(Some(&update(&[61, 13, 29]))),
+            "digraph update {
+    rankdir=LR;
+    61 [label=\"61 @0\"];
+    13 [label=\"13 @1\"];
+    29 [label=\"29 @2\"];
+    29 -> 13 [color=red];
+    61 -> 13;
+    61 -> 29;
+}
+"
+        );
+    }
+
+    #[test]
+    fn sample_orderings_are_unique() {
+        let (order, updates) = parse_input(SAMPLE).unwrap();
+        for upd in updates.iter() {
+            let analysis = order.analyze_ordering(upd).unwrap();
+            assert!(analysis.is_unique());
+            assert!(analysis.unconstrained.is_empty());
+            assert_eq!(
+                analysis.possible_midpoints,
+                vec![order.topo(upd).unwrap().midpoint()]
+            );
+        }
+    }
+
+    #[test]
+    fn partial_rules_leave_choices() {
+        // 1 before 2 and 3, which are free relative to each other, and 4 is free entirely.
+        let mut order = OrderRules::new();
+        order.add_rule(1, 2);
+        order.add_rule(1, 3);
+        let analysis = order.analyze_ordering(&update(&[3, 4, 2, 1])).unwrap();
+        assert_eq!(analysis.count, OrderingCount::Exactly(8));
+        assert_eq!(analysis.unconstrained, vec![(1, 4), (2, 3), (2, 4), (3, 4)]);
+        assert_eq!(analysis.possible_midpoints, vec![2, 3, 4]);
+        assert!(!analysis.midpoint_is_defined());
+    }
+
+    #[test]
+    fn midpoint_can_be_defined_without_unique_order() {
+        // 1 and 2 in either order, then 3, then 4 and 5 in either order.
+        let mut order = OrderRules::new();
+        for (before, after) in [(1, 3), (2, 3), (3, 4), (3, 5)] {
+            order.add_rule(before, after);
+        }
+        let analysis = order.analyze_ordering(&update(&[5, 4, 3, 2, 1])).unwrap();
+        assert_eq!(analysis.count, OrderingCount::Exactly(4));
+        assert!(!analysis.is_unique());
+        assert!(analysis.midpoint_is_defined());
+        assert_eq!(analysis.possible_midpoints, vec![3]);
+    }
+
+    #[test]
+    fn no_rules_at_all() {
+        let order = OrderRules::new();
+        let analysis = order.analyze_ordering(&update(&[1, 2, 3, 4, 5])).unwrap();
+        assert_eq!(analysis.count, OrderingCount::Exactly(120));
+        assert_eq!(analysis.possible_midpoints, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn counting_many_pages_is_cheap() {
+        // No rules: each page is its own piece, so it's just 31!.
+        let order = OrderRules::new();
+        let pages: Vec<u32> = (1..=31).collect();
+        let analysis = order.analyze_ordering(&update(&pages)).unwrap();
+        let factorial = (1..=31).product::<u128>();
+        assert_eq!(analysis.count, OrderingCount::Exactly(factorial));
+        assert!(!analysis.is_unique());
+
+        // 99 single-page pieces, each counted well under `ORDERING_CAP`.
+        // Only combining them runs past a u128.
+        let pages: Vec<u32> = (1..=99).collect();
+        let analysis = order.analyze_ordering(&update(&pages)).unwrap();
+        assert_eq!(analysis.count, OrderingCount::AtLeast(u128::MAX));
+        assert_eq!(analysis.possible_midpoints.len(), 99);
+    }
+
+    #[test]
+    fn counting_stops_at_the_cap() {
+        // One page before all the others, which are then free: 40! orderings
+        // in a single piece.
+        let mut order = OrderRules::new();
+        for after in 2..=41 {
+            order.add_rule(1, after);
+        }
+        let pages: Vec<u32> = (1..=41).collect();
+        let analysis = order.analyze_ordering(&update(&pages)).unwrap();
+        assert_eq!(analysis.count, OrderingCount::AtLeast(ORDERING_CAP));
+        assert_eq!(
+            analysis.count.to_string(),
+            format!("at least {}", ORDERING_CAP)
+        );
+    }
+
+    #[test]
+    fn long_chain_is_unique() {
+        let mut order = OrderRules::new();
+        for p in 1..80 {
+            order.add_rule(p, p + 1);
+        }
+        let pages: Vec<u32> = (1..=79).rev().collect();
+        let analysis = order.analyze_ordering(&update(&pages)).unwrap();
+        assert!(analysis.is_unique());
+        assert_eq!(analysis.count, OrderingCount::Exactly(1));
+        assert!(analysis.midpoint_is_defined());
+    }
+
+    #[test]
+    fn binomials() {
+        assert_eq!(binomial(5, 2), Some(10));
+        assert_eq!(binomial(7, 0), Some(1));
+        assert_eq!(binomial(7, 7), Some(1));
+        assert_eq!(binomial(30, 15), Some(155_117_520));
+        // Fits, though n * C(n - 1, k - 1) doesn't.
+        assert_eq!(
+            binomial(130, 65),
+            Some(95_067_625_827_960_698_145_584_333_020_095_113_100)
+        );
+        assert_eq!(binomial(135, 67), None);
+    }
+
+    #[test]
+    fn pieces_are_shuffled_together() {
+        // 1 < 2 < 3 and 4 < 5: 5! / (3! 2!) interleavings.
+        let mut order = OrderRules::new();
+        for (before, after) in [(1, 2), (2, 3), (4, 5)] {
+            order.add_rule(before, after);
+        }
+        let analysis = order.analyze_ordering(&update(&[5, 4, 3, 2, 1])).unwrap();
+        assert_eq!(analysis.count, OrderingCount::Exactly(10));
+        assert!(!analysis.is_unique());
+    }
+
+    fn update_err(s: &str) -> (usize, &'static str) {
+        let e = s.parse::<PageUpdate>().unwrap_err();
+        return (e.col, e.reason);
+    }
+
+    #[test]
+    fn update_parse_errors() {
+        assert_eq!(update_err(""), (1, "Empty update"));
+        assert_eq!(update_err("75,47,61,"), (9, "Trailing comma"));
+        assert_eq!(update_err("75,,61"), (4, "Missing page"));
+        assert_eq!(update_err("75,4x,61"), (4, "Not a page number"));
+        assert_eq!(update_err("75,47,75"), (7, "Duplicate page"));
+        assert_eq!(
+            update_err("75,47"),
+            (1, "Even number of pages, no midpoint")
+        );
+        assert_eq!("75,47,61".parse(), Ok(update(&[75, 47, 61])));
+    }
+
+    #[test]
+    fn input_parse_errors_have_lines() {
+        assert_eq!(
+            parse_input("1|2\n\n1,2,3\n\n").err(),
+            Some(ParseError {
+                line: 4,
+                col: 1,
+                reason: "Empty update",
+            })
+        );
+        assert_eq!(
+            parse_input("1|2\n3|x\n\n1").err(),
+            Some(ParseError {
+                line: 2,
+                col: 3,
+                reason: "After bad",
+            })
+        );
+        assert_eq!(
+            parse_input("1|2\n\n1,2,3,4").err().unwrap().to_string(),
+            "line 3, column 1: Even number of pages, no midpoint"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "No midpoint")]
+    fn midpoint_of_even_update() {
+        update(&[1, 2]).midpoint();
+    }
 }
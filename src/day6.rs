@@ -1,35 +1,29 @@
-use std::collections::HashSet;
+use std::fmt::Display;
 use std::str::FromStr;
 
-use crate::grid::Direction;
+use crate::answer::Answer;
+use crate::bitset::GridBitSet;
+use crate::grid::Dir4;
 use crate::grid::Grid;
 use crate::grid::Point;
-use crate::grid::DOWN;
-use crate::grid::LEFT;
-use crate::grid::RIGHT;
-use crate::grid::UP;
 
 const INPUT_1: &'static str = include_str!("day6-1.txt");
 
-#[derive(Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 enum Loc {
-    Guard(&'static Direction),
+    Guard(Dir4),
     Obstruction,
     Empty,
 }
 
 impl Loc {
     fn is_guard(&self) -> bool {
-        if let Loc::Guard(_) = self {
-            true
-        } else {
-            false
-        }
+        return matches!(self, Loc::Guard(_));
     }
 
-    fn direction(&self) -> Option<&'static Direction> {
+    fn direction(&self) -> Option<Dir4> {
         match self {
-            Loc::Guard(x) => Some(x),
+            Loc::Guard(x) => Some(*x),
             Loc::Obstruction | Loc::Empty => None,
         }
     }
@@ -44,55 +38,229 @@ impl FromStr for Loc {
         }
 
         return Ok(match s.chars().nth(0).unwrap() {
-            '^' => Loc::Guard(&UP),
-            '>' => Loc::Guard(&RIGHT),
-            '<' => Loc::Guard(&LEFT),
-            'V' => Loc::Guard(&DOWN),
             '#' => Loc::Obstruction,
             '.' => Loc::Empty,
-            _ => return Err("Unrecognized!"),
+            // Older inputs draw a downward guard as a capital.
+            'V' => Loc::Guard(Dir4::Down),
+            c => Loc::Guard(Dir4::try_from(c).map_err(|_| "Unrecognized!")?),
         });
     }
 }
 
+// Round-trips with `from_str`, so grids of these can be diffed and printed.
+impl Display for Loc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let c = match self {
+            Loc::Guard(d) => d.glyph(),
+            Loc::Obstruction => '#',
+            Loc::Empty => '.',
+        };
+        return write!(f, "{}", c);
+    }
+}
+
+type Guard = (Point, Dir4);
+
 fn guard(g: &Grid<Loc>, p: &Point) -> bool {
-    return g.at(p).map_or(false, Loc::is_guard);
+    return g.at(p).is_some_and(Loc::is_guard);
 }
 
-pub fn run_p1() {
+fn find_guard(g: &Grid<Loc>) -> Guard {
+    let pos = g.search(guard).next().expect("A Guard");
+    let dir = g.at(&pos).unwrap().direction().unwrap();
+    return (pos, dir);
+}
+
+// Turning in place counts as its own step, so being boxed in is just a cycle
+// rather than spinning forever. `None` once the guard walks off the map.
+fn step(g: &Grid<Loc>, (pos, dir): &Guard) -> Option<Guard> {
+    let potential_nxt = pos.add(*dir);
+    return match g.at(&potential_nxt) {
+        Some(Loc::Obstruction) => Some((pos.clone(), dir.rotate90())),
+        Some(Loc::Guard(_)) | Some(Loc::Empty) => Some((potential_nxt, *dir)),
+        None => None,
+    };
+}
+
+pub fn run_p1() -> Answer {
     let g: Grid<Loc> = INPUT_1.parse().unwrap();
 
-    let mut guard_pos = g.search(guard).nth(0).expect("A Guard");
-    let mut guard_dir = g.at(&guard_pos).unwrap().direction().unwrap();
-    let mut visited_posns = HashSet::new();
-
-    'full_search: loop {
-        visited_posns.insert(guard_pos.clone());
-        let mut potential_nxt = guard_pos.add(guard_dir);
-
-        // A corner means we might need to turn a few times.
-        // In a real solution I'd check that we aren't fully boxed in
-        // But this only requires solving for a single input where that isn't the case
-        loop {
-            match g.at(&potential_nxt) {
-                Some(Loc::Obstruction) => {
-                    // Turn and (try) move forward
-                    guard_dir = guard_dir.rotate90();
-                    potential_nxt = guard_pos.add(guard_dir);
-                },
-                Some(Loc::Guard(_)) | Some(Loc::Empty) => {
-                    guard_pos = potential_nxt.clone();
-                    // Found a spot so we break inner loop.
-                    break;
-                },
-                None => {
-                    break 'full_search;
-                },
-            }
-        }
+    let mut visited_posns = GridBitSet::new(&g);
+    for (pos, _) in std::iter::successors(Some(find_guard(&g)), |s| step(&g, s)) {
+        visited_posns.insert(&pos);
     }
 
-    println!("Day 6, Part 1: {}", visited_posns.len());
+    return visited_posns.count().into();
+}
+
+pub fn run_p2() -> Answer {
+    return Answer::NotImplemented;
 }
 
-pub fn run_p2() {}
+#[cfg(test)]
+mod test {
+    use crate::bitset::GridBitSet;
+    use crate::cycle::detect_brent;
+    use crate::cycle::detect_floyd;
+    use crate::cycle::detect_hashed;
+    use crate::cycle::nth_state;
+    use crate::day6::find_guard;
+    use crate::day6::step;
+    use crate::day6::Loc;
+    use crate::diff::assert_grid_eq;
+    use crate::diff::diff_sets;
+    use crate::grid::Dir4;
+    use crate::grid::Grid;
+    use crate::grid::Point;
+
+    const SAMPLE: &'static str = "\
+....#.....
+.........#
+..........
+..#.......
+.......#..
+..........
+.#..^.....
+........#.
+#.........
+......#...";
+
+    // Same as the sample, with an obstruction next to the guard that traps it.
+    const SAMPLE_LOOP: &'static str = "\
+....#.....
+.........#
+..........
+..#.......
+.......#..
+..........
+.#.#^.....
+........#.
+#.........
+......#...";
+
+    #[test]
+    fn sample_walks_off_the_map() {
+        let g: Grid<Loc> = SAMPLE.parse().unwrap();
+        assert_eq!(detect_hashed(find_guard(&g), |s| step(&g, s)), None);
+        assert_eq!(detect_floyd(find_guard(&g), |s| step(&g, s)), None);
+        assert_eq!(detect_brent(find_guard(&g), |s| step(&g, s)), None);
+    }
+
+    #[test]
+    fn obstruction_traps_the_guard() {
+        let g: Grid<Loc> = SAMPLE_LOOP.parse().unwrap();
+        let hashed = detect_hashed(find_guard(&g), |s| step(&g, s));
+        assert!(hashed.is_some());
+        assert_eq!(detect_floyd(find_guard(&g), |s| step(&g, s)), hashed);
+        assert_eq!(detect_brent(find_guard(&g), |s| step(&g, s)), hashed);
+    }
+
+    #[test]
+    fn trapped_guard_skips_ahead() {
+        let g: Grid<Loc> = SAMPLE_LOOP.parse().unwrap();
+        let cycle = detect_hashed(find_guard(&g), |s| step(&g, s)).unwrap();
+        let far = 4_000_000_000;
+        let walked = std::iter::successors(Some(find_guard(&g)), |s| step(&g, s))
+            .nth(cycle.reduce(far))
+            .unwrap();
+        assert_eq!(
+            nth_state(find_guard(&g), |s| step(&g, s), far),
+            Some(walked)
+        );
+    }
+
+    #[test]
+    fn guard_glyphs() {
+        for (c, d) in [('^', Dir4::Up), ('>', Dir4::Right), ('<', Dir4::Left)] {
+            assert_eq!(c.to_string().parse(), Ok(Loc::Guard(d)));
+        }
+        assert_eq!("v".parse(), Ok(Loc::Guard(Dir4::Down)));
+        assert_eq!("V".parse(), Ok(Loc::Guard(Dir4::Down)));
+        assert_eq!("x".parse::<Loc>(), Err("Unrecognized!"));
+    }
+
+    // Cells the guard stands on, stopping once it leaves or starts repeating.
+    fn walked(g: &Grid<Loc>) -> GridBitSet {
+        let steps = match detect_hashed(find_guard(g), |s| step(g, s)) {
+            Some(cycle) => cycle.start + cycle.length,
+            None => usize::MAX,
+        };
+        let mut out = GridBitSet::new(g);
+        for (pos, _) in std::iter::successors(Some(find_guard(g)), |s| step(g, s)).take(steps) {
+            out.insert(&pos);
+        }
+        return out;
+    }
+
+    #[test]
+    fn sample_walk_matches_puzzle() {
+        // The path as drawn in the puzzle.
+        let drawn: Grid<char> = "\
+....#.....
+....XXXXX#
+....X...X.
+..#.X...X.
+..XXXXX#X.
+..X.X.X.X.
+.#XXXXXXX.
+.XXXXXXX#.
+#XXXXXXX..
+......#X.."
+            .parse()
+            .unwrap();
+        let expected: GridBitSet = {
+            let mut s = GridBitSet::new(&drawn);
+            for p in drawn.search(|g, p| g.at(p) == Some(&'X')) {
+                s.insert(&p);
+            }
+            s
+        };
+
+        let g: Grid<Loc> = SAMPLE.parse().unwrap();
+        let d = diff_sets(&expected, &walked(&g));
+        assert!(d.is_empty(), "Walk differs:\n{}", d.highlight(&g));
+        assert_eq!(expected.count(), 41);
+    }
+
+    #[test]
+    fn trapped_walk_against_free_walk() {
+        let free: Grid<Loc> = SAMPLE.parse().unwrap();
+        let trapped: Grid<Loc> = SAMPLE_LOOP.parse().unwrap();
+        // Trapped, it only ever circles the top right, so the rest of the
+        // free walk is gone.
+        let d = diff_sets(&walked(&free), &walked(&trapped));
+        assert_eq!(
+            d.highlight(&trapped),
+            "\
+....#.....
+.........#
+..........
+..#.......
+..--.--#..
+..-...-...
+.#--^.....
+.-------#.
+#-------..
+......#-..
+"
+        );
+    }
+
+    #[test]
+    fn grid_round_trips_through_display() {
+        let g: Grid<Loc> = SAMPLE_LOOP.parse().unwrap();
+        let mut drawn = String::new();
+        for y in 0..g.height() {
+            for x in 0..g.width() {
+                drawn.push_str(
+                    &g.at(&Point::new(x as isize, y as isize))
+                        .unwrap()
+                        .to_string(),
+                );
+            }
+            drawn.push('\n');
+        }
+        assert_eq!(drawn.trim_end(), SAMPLE_LOOP);
+        assert_grid_eq(&g, &drawn.parse().unwrap());
+    }
+}
@@ -0,0 +1,247 @@
+use std::fmt::Display;
+use std::fmt::Write;
+
+use crate::bitset::GridBitSet;
+use crate::grid::Grid;
+use crate::grid::Point;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CellDiff<T> {
+    // Only on the right.
+    Added(T),
+    // Only on the left.
+    Removed(T),
+    Changed(T, T),
+}
+
+impl<T> CellDiff<T> {
+    fn marker(&self) -> char {
+        return match self {
+            CellDiff::Added(_) => '+',
+            CellDiff::Removed(_) => '-',
+            CellDiff::Changed(_, _) => '~',
+        };
+    }
+}
+
+// Cells that differ between two grids, in reading order.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GridDiff<T> {
+    pub cells: Vec<(Point, CellDiff<T>)>,
+}
+
+pub fn diff<T>(left: &Grid<T>, right: &Grid<T>) -> GridDiff<T>
+where
+    T: Clone + PartialEq,
+{
+    let mut cells = Vec::new();
+    for y in 0..left.height().max(right.height()) {
+        for x in 0..left.width().max(right.width()) {
+            let p = Point::new(x as isize, y as isize);
+            let change = match (left.get(&p), right.get(&p)) {
+                (Some(l), Some(r)) if l == r => continue,
+                (Some(l), Some(r)) => CellDiff::Changed(l.clone(), r.clone()),
+                (Some(l), None) => CellDiff::Removed(l.clone()),
+                (None, Some(r)) => CellDiff::Added(r.clone()),
+                (None, None) => continue,
+            };
+            cells.push((p, change));
+        }
+    }
+
+    return GridDiff { cells };
+}
+
+// Points only in `right` are added, only in `left` removed.
+pub fn diff_sets(left: &GridBitSet, right: &GridBitSet) -> GridDiff<()> {
+    let mut added = right.clone();
+    added.difference_with(left);
+    let mut removed = left.clone();
+    removed.difference_with(right);
+
+    let mut cells: Vec<(Point, CellDiff<()>)> = added
+        .iter()
+        .map(|p| (p, CellDiff::Added(())))
+        .chain(removed.iter().map(|p| (p, CellDiff::Removed(()))))
+        .collect();
+    cells.sort_by_key(|(p, _)| (p.y(), p.x()));
+    return GridDiff { cells };
+}
+
+impl<T> GridDiff<T> {
+    pub fn is_empty(&self) -> bool {
+        return self.cells.is_empty();
+    }
+
+    // `cells` is in reading order, so this can search rather than scan.
+    pub fn at(&self, p: &Point) -> Option<&CellDiff<T>> {
+        let key = |q: &Point| (q.y(), q.x());
+        return self
+            .cells
+            .binary_search_by_key(&key(p), |(q, _)| key(q))
+            .ok()
+            .map(|idx| &self.cells[idx].1);
+    }
+
+    // `base` with every differing cell replaced by `+`, `-` or `~`.
+    pub fn highlight<B: Display>(&self, base: &Grid<B>) -> String {
+        let mut out = String::new();
+        let height = base.height().max(self.extent(|p| p.y()));
+        let width = base.width().max(self.extent(|p| p.x()));
+        for y in 0..height {
+            for x in 0..width {
+                let p = Point::new(x as isize, y as isize);
+                match (self.at(&p), base.get(&p)) {
+                    (Some(c), _) => out.push(c.marker()),
+                    (None, Some(b)) => write!(out, "{}", b).unwrap(),
+                    (None, None) => out.push(' '),
+                }
+            }
+            out.push('\n');
+        }
+        return out;
+    }
+
+    fn extent(&self, coord: impl Fn(&Point) -> isize) -> usize {
+        return self
+            .cells
+            .iter()
+            .map(|(p, _)| coord(p) as usize + 1)
+            .max()
+            .unwrap_or(0);
+    }
+}
+
+impl<T: Display> Display for GridDiff<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (p, c) in self.cells.iter() {
+            write!(f, "({}, {}) ", p.x(), p.y())?;
+            match c {
+                CellDiff::Added(r) => writeln!(f, "+ {}", r)?,
+                CellDiff::Removed(l) => writeln!(f, "- {}", l)?,
+                CellDiff::Changed(l, r) => writeln!(f, "~ {} -> {}", l, r)?,
+            }
+        }
+        return Ok(());
+    }
+}
+
+// Both grids next to each other, with rows that differ flagged on the right.
+pub fn side_by_side<T>(left: &Grid<T>, right: &Grid<T>) -> String
+where
+    T: Clone + Display + PartialEq,
+{
+    let d = diff(left, right);
+    let render_row = |g: &Grid<T>, y: usize, width: usize| {
+        let mut row = String::new();
+        for x in 0..width {
+            match g.get(&Point::new(x as isize, y as isize)) {
+                Some(v) => write!(row, "{}", v).unwrap(),
+                None => row.push(' '),
+            }
+        }
+        return row;
+    };
+
+    let mut out = String::new();
+    for y in 0..left.height().max(right.height()) {
+        let differs = d.cells.iter().any(|(p, _)| p.y() as usize == y);
+        writeln!(
+            out,
+            "{} | {}{}",
+            render_row(left, y, left.width()),
+            render_row(right, y, right.width()),
+            if differs { " <" } else { "" }
+        )
+        .unwrap();
+    }
+    return out;
+}
+
+#[track_caller]
+pub fn assert_grid_eq<T>(left: &Grid<T>, right: &Grid<T>)
+where
+    T: Clone + Display + PartialEq,
+{
+    let d = diff(left, right);
+    if !d.is_empty() {
+        panic!(
+            "Grids differ:\n{}\n{}",
+            side_by_side(left, right),
+            d.to_string().trim_end()
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bitset::GridBitSet;
+    use crate::diff::assert_grid_eq;
+    use crate::diff::diff;
+    use crate::diff::diff_sets;
+    use crate::diff::side_by_side;
+    use crate::diff::CellDiff;
+    use crate::grid::Grid;
+    use crate::grid::Point;
+
+    fn grid(s: &str) -> Grid<char> {
+        return s.parse().unwrap();
+    }
+
+    #[test]
+    fn identical_grids() {
+        assert!(diff(&grid("ab\ncd"), &grid("ab\ncd")).is_empty());
+        assert_grid_eq(&grid("ab\ncd"), &grid("ab\ncd"));
+    }
+
+    #[test]
+    fn added_removed_changed() {
+        let d = diff(&grid("ab\ncd\ne"), &grid("ax\ncdf"));
+        assert_eq!(
+            d.cells,
+            vec![
+                (Point::new(1, 0), CellDiff::Changed('b', 'x')),
+                (Point::new(2, 1), CellDiff::Added('f')),
+                (Point::new(0, 2), CellDiff::Removed('e')),
+            ]
+        );
+        assert_eq!(d.to_string(), "(1, 0) ~ b -> x\n(2, 1) + f\n(0, 2) - e\n");
+        assert_eq!(d.at(&Point::new(2, 1)), Some(&CellDiff::Added('f')));
+        assert_eq!(d.at(&Point::new(0, 2)), Some(&CellDiff::Removed('e')));
+        assert_eq!(d.at(&Point::new(0, 0)), None);
+    }
+
+    #[test]
+    fn highlighted_and_side_by_side() {
+        let left = grid("...\n.#.");
+        let right = grid("..#\n.#.");
+        assert_eq!(diff(&left, &right).highlight(&left), "..~\n.#.\n");
+        assert_eq!(side_by_side(&left, &right), "... | ..# <\n.#. | .#.\n");
+    }
+
+    #[test]
+    fn point_sets() {
+        let g = grid("....\n....");
+        let mut left = GridBitSet::new(&g);
+        let mut right = GridBitSet::new(&g);
+        left.insert(&Point::new(0, 0));
+        left.insert(&Point::new(1, 1));
+        right.insert(&Point::new(1, 1));
+        right.insert(&Point::new(3, 0));
+        let d = diff_sets(&left, &right);
+        assert_eq!(
+            d.cells,
+            vec![
+                (Point::new(0, 0), CellDiff::Removed(())),
+                (Point::new(3, 0), CellDiff::Added(())),
+            ]
+        );
+        assert_eq!(d.highlight(&g), "-..+\n....\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "(1, 0) ~ b -> x")]
+    fn assert_reports_cells() {
+        assert_grid_eq(&grid("ab"), &grid("ax"));
+    }
+}
@@ -0,0 +1,377 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::hash::Hash;
+
+// Adjacency both ways, so predecessors are as cheap as successors.
+// Every node has an entry in both maps, even with no edges.
+#[derive(Clone, Debug)]
+pub struct DiGraph<N> {
+    succ: HashMap<N, HashSet<N>>,
+    pred: HashMap<N, HashSet<N>>,
+}
+
+impl<N> Default for DiGraph<N> {
+    fn default() -> Self {
+        return DiGraph {
+            succ: HashMap::new(),
+            pred: HashMap::new(),
+        };
+    }
+}
+
+impl<N> DiGraph<N>
+where
+    N: Clone + Eq + Hash,
+{
+    pub fn new() -> DiGraph<N> {
+        return DiGraph::default();
+    }
+
+    // Returns whether the node is new.
+    pub fn add_node(&mut self, n: N) -> bool {
+        if self.succ.contains_key(&n) {
+            return false;
+        }
+
+        self.succ.insert(n.clone(), HashSet::new());
+        self.pred.insert(n, HashSet::new());
+        return true;
+    }
+
+    // Adds both ends as needed. Returns whether the edge is new.
+    pub fn add_edge(&mut self, from: N, to: N) -> bool {
+        self.add_node(from.clone());
+        self.add_node(to.clone());
+        self.pred.get_mut(&to).unwrap().insert(from.clone());
+        return self.succ.get_mut(&from).unwrap().insert(to);
+    }
+
+    // Leaves both ends in place. Returns whether the edge was there.
+    pub fn remove_edge(&mut self, from: &N, to: &N) -> bool {
+        if let Some(preds) = self.pred.get_mut(to) {
+            preds.remove(from);
+        }
+        return self
+            .succ
+            .get_mut(from)
+            .is_some_and(|succs| succs.remove(to));
+    }
+
+    // Along with every edge touching it. Returns whether the node was there.
+    pub fn remove_node(&mut self, n: &N) -> bool {
+        let Some(succs) = self.succ.remove(n) else {
+            return false;
+        };
+        let preds = self.pred.remove(n).unwrap();
+        for s in succs.iter() {
+            self.pred.get_mut(s).unwrap().remove(n);
+        }
+        for p in preds.iter() {
+            self.succ.get_mut(p).unwrap().remove(n);
+        }
+        return true;
+    }
+
+    pub fn contains_node(&self, n: &N) -> bool {
+        return self.succ.contains_key(n);
+    }
+
+    pub fn has_edge(&self, from: &N, to: &N) -> bool {
+        return self.succ.get(from).is_some_and(|succs| succs.contains(to));
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &N> + '_ {
+        return self.succ.keys();
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = (&N, &N)> + '_ {
+        return self
+            .succ
+            .iter()
+            .flat_map(|(from, succs)| succs.iter().map(move |to| (from, to)));
+    }
+
+    pub fn node_count(&self) -> usize {
+        return self.succ.len();
+    }
+
+    pub fn edge_count(&self) -> usize {
+        return self.succ.values().map(HashSet::len).sum();
+    }
+
+    // Empty for unknown nodes.
+    pub fn successors<'a>(&'a self, n: &N) -> impl Iterator<Item = &'a N> + 'a {
+        return self.succ.get(n).into_iter().flatten();
+    }
+
+    // Empty for unknown nodes.
+    pub fn predecessors<'a>(&'a self, n: &N) -> impl Iterator<Item = &'a N> + 'a {
+        return self.pred.get(n).into_iter().flatten();
+    }
+
+    // The given nodes, whether or not they're in this graph, and the edges between them.
+    pub fn induced(&self, nodes: impl IntoIterator<Item = N>) -> DiGraph<N> {
+        let mut out = DiGraph::new();
+        for n in nodes {
+            out.add_node(n);
+        }
+
+        let keep: Vec<N> = out.nodes().cloned().collect();
+        for from in keep.iter() {
+            for to in self.successors(from) {
+                if out.contains_node(to) {
+                    out.add_edge(from.clone(), to.clone());
+                }
+            }
+        }
+        return out;
+    }
+
+    // Everything reachable through at least one edge, so `n` itself only if
+    // it's on a cycle.
+    pub fn reachable_from(&self, n: &N) -> HashSet<N> {
+        let mut seen = HashSet::new();
+        let mut stack = vec![n];
+        while let Some(cur) = stack.pop() {
+            for s in self.successors(cur) {
+                if seen.insert(s.clone()) {
+                    stack.push(s);
+                }
+            }
+        }
+        return seen;
+    }
+
+    pub fn transitive_closure(&self) -> DiGraph<N> {
+        let mut out = DiGraph::new();
+        for from in self.nodes() {
+            out.add_node(from.clone());
+            for to in self.reachable_from(from) {
+                out.add_edge(from.clone(), to);
+            }
+        }
+        return out;
+    }
+
+    // Tarjan's, iteratively. Components come out in reverse topological order.
+    pub fn sccs(&self) -> Vec<Vec<N>> {
+        let mut next_index = 0;
+        let mut index: HashMap<&N, usize> = HashMap::new();
+        let mut low: HashMap<&N, usize> = HashMap::new();
+        let mut stack: Vec<&N> = Vec::new();
+        let mut on_stack: HashSet<&N> = HashSet::new();
+        let mut out = Vec::new();
+
+        for root in self.nodes() {
+            if index.contains_key(root) {
+                continue;
+            }
+
+            // Each frame is a node and how many of its successors we've looked at.
+            let mut calls: Vec<(&N, Vec<&N>, usize)> = Vec::new();
+            let mut visit = Some(root);
+            loop {
+                if let Some(v) = visit.take() {
+                    index.insert(v, next_index);
+                    low.insert(v, next_index);
+                    next_index += 1;
+                    stack.push(v);
+                    on_stack.insert(v);
+                    calls.push((v, self.successors(v).collect(), 0));
+                }
+
+                let Some((v, succs, seen)) = calls.last_mut() else {
+                    break;
+                };
+                let v = *v;
+                if let Some(w) = succs.get(*seen).copied() {
+                    *seen += 1;
+                    if !index.contains_key(w) {
+                        visit = Some(w);
+                    } else if on_stack.contains(w) {
+                        let lowest = low[v].min(index[w]);
+                        low.insert(v, lowest);
+                    }
+                    continue;
+                }
+
+                calls.pop();
+                if let Some((parent, _, _)) = calls.last() {
+                    let lowest = low[parent].min(low[v]);
+                    low.insert(parent, lowest);
+                }
+                if low[v] == index[v] {
+                    let mut component = Vec::new();
+                    loop {
+                        let w = stack.pop().unwrap();
+                        on_stack.remove(w);
+                        component.push(w.clone());
+                        if w == v {
+                            break;
+                        }
+                    }
+                    out.push(component);
+                }
+            }
+        }
+        return out;
+    }
+
+    pub fn topo_order(&self) -> Result<Vec<N>, Vec<N>> {
+        return self.topo_order_by(|_| ());
+    }
+
+    // Kahn's algorithm, taking the lowest `key` whenever there's a choice.
+    // On failure, returns a cycle with each node having an edge to the next
+    // and the last to the first.
+    pub fn topo_order_by<K: Ord>(&self, key: impl Fn(&N) -> K) -> Result<Vec<N>, Vec<N>> {
+        // Heap entries carry an index so ties don't need `N: Ord`.
+        let nodes: Vec<&N> = self.nodes().collect();
+        let node_idx: HashMap<&N, usize> = nodes.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+        let mut in_degree: Vec<usize> = nodes.iter().map(|n| self.pred[*n].len()).collect();
+
+        let mut ready: BinaryHeap<Reverse<(K, usize)>> = (0..nodes.len())
+            .filter(|i| in_degree[*i] == 0)
+            .map(|i| Reverse((key(nodes[i]), i)))
+            .collect();
+        let mut sorted = Vec::with_capacity(nodes.len());
+        while let Some(Reverse((_, i))) = ready.pop() {
+            sorted.push(nodes[i].clone());
+            for s in self.successors(nodes[i]) {
+                let s_idx = node_idx[s];
+                in_degree[s_idx] -= 1;
+                if in_degree[s_idx] == 0 {
+                    ready.push(Reverse((key(s), s_idx)));
+                }
+            }
+        }
+
+        if sorted.len() < nodes.len() {
+            let stuck: HashSet<&N> = (0..nodes.len())
+                .filter(|i| in_degree[*i] > 0)
+                .map(|i| nodes[i])
+                .collect();
+            return Err(self.cycle_within(&stuck, &key));
+        }
+
+        return Ok(sorted);
+    }
+
+    // Every node Kahn's couldn't place still waits on another such node,
+    // so walking those predecessors backwards has to come round in a loop.
+    fn cycle_within<K: Ord>(&self, stuck: &HashSet<&N>, key: &impl Fn(&N) -> K) -> Vec<N> {
+        let mut path: Vec<&N> = Vec::new();
+        let mut path_idx: HashMap<&N, usize> = HashMap::new();
+        let mut cur = *stuck.iter().min_by_key(|n| key(n)).unwrap();
+        while !path_idx.contains_key(cur) {
+            path_idx.insert(cur, path.len());
+            path.push(cur);
+            cur = self
+                .predecessors(cur)
+                .filter(|p| stuck.contains(p))
+                .min_by_key(|p| key(p))
+                .unwrap();
+        }
+
+        // Walked it backwards, so flip it round and start where the loop closed.
+        let mut cycle: Vec<N> = path.split_off(path_idx[cur]).into_iter().cloned().collect();
+        cycle.reverse();
+        cycle.rotate_right(1);
+        return cycle;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use crate::digraph::DiGraph;
+
+    fn graph(edges: &[(u32, u32)]) -> DiGraph<u32> {
+        let mut g = DiGraph::new();
+        for (from, to) in edges.iter() {
+            g.add_edge(*from, *to);
+        }
+        return g;
+    }
+
+    fn sorted<'a>(it: impl Iterator<Item = &'a u32>) -> Vec<u32> {
+        let mut v: Vec<u32> = it.copied().collect();
+        v.sort();
+        return v;
+    }
+
+    #[test]
+    fn edges_both_ways() {
+        let mut g = graph(&[(1, 2), (1, 3), (3, 2)]);
+        assert!(!g.add_edge(1, 2));
+        assert_eq!(g.edge_count(), 3);
+        assert_eq!(sorted(g.successors(&1)), vec![2, 3]);
+        assert_eq!(sorted(g.predecessors(&2)), vec![1, 3]);
+        assert_eq!(g.successors(&7).count(), 0);
+
+        assert!(g.remove_edge(&1, &2));
+        assert!(!g.remove_edge(&1, &2));
+        assert_eq!(sorted(g.predecessors(&2)), vec![3]);
+        assert!(g.contains_node(&1));
+
+        assert!(g.remove_node(&3));
+        assert_eq!(g.node_count(), 2);
+        assert_eq!(g.edge_count(), 0);
+        assert_eq!(g.successors(&1).count(), 0);
+    }
+
+    #[test]
+    fn induced_keeps_unknown_nodes() {
+        let g = graph(&[(1, 2), (2, 3), (3, 4)]);
+        let sub = g.induced([1, 3, 4, 9]);
+        assert_eq!(sorted(sub.nodes()), vec![1, 3, 4, 9]);
+        assert!(sub.has_edge(&3, &4));
+        assert_eq!(sub.edge_count(), 1);
+    }
+
+    #[test]
+    fn reachability() {
+        let g = graph(&[(1, 2), (2, 3), (3, 2), (4, 1)]);
+        assert_eq!(g.reachable_from(&1), HashSet::from([2, 3]));
+        assert_eq!(g.reachable_from(&2), HashSet::from([2, 3]));
+        let closure = g.transitive_closure();
+        assert!(closure.has_edge(&4, &3));
+        assert!(!closure.has_edge(&1, &1));
+        assert_eq!(closure.edge_count(), 9);
+    }
+
+    #[test]
+    fn strongly_connected_components() {
+        let g = graph(&[(1, 2), (2, 3), (3, 1), (3, 4), (4, 5), (5, 4), (6, 6)]);
+        let mut comps: Vec<Vec<u32>> = g.sccs().into_iter().map(|c| sorted(c.iter())).collect();
+        comps.sort();
+        assert_eq!(comps, vec![vec![1, 2, 3], vec![4, 5], vec![6]]);
+
+        // Reverse topological, so {4, 5} before {1, 2, 3}.
+        let order = g.sccs();
+        let at = |n: u32| order.iter().position(|c| c.contains(&n)).unwrap();
+        assert!(at(4) < at(1));
+    }
+
+    #[test]
+    fn topo_order_by_key() {
+        let g = graph(&[(3, 1), (2, 1), (4, 2)]);
+        assert_eq!(g.topo_order_by(|n| *n), Ok(vec![3, 4, 2, 1]));
+        assert_eq!(g.topo_order_by(|n| Some(10 - n)), Ok(vec![4, 3, 2, 1]));
+        let any = g.topo_order().unwrap();
+        assert!(g
+            .edges()
+            .all(|(a, b)| any.iter().position(|n| n == a) < any.iter().position(|n| n == b)));
+    }
+
+    #[test]
+    fn topo_order_reports_cycle() {
+        let g = graph(&[(0, 1), (1, 2), (2, 3), (3, 1), (3, 4)]);
+        let cycle = g.topo_order_by(|n| *n).unwrap_err();
+        assert_eq!(cycle, vec![1, 2, 3]);
+        assert_eq!(graph(&[(5, 5)]).topo_order(), Err(vec![5]));
+    }
+}
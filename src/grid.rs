@@ -1,45 +1,189 @@
 use std::str::FromStr;
 
-pub const DOWN_RIGHT: Direction = Direction(1, 1);
-pub const UP_LEFT: Direction = Direction(-1, -1);
-pub const DOWN_LEFT: Direction = Direction(-1, 1);
-pub const UP_RIGHT: Direction = Direction(1, -1);
-pub const UP: Direction = Direction(0, -1);
-pub const DOWN: Direction = Direction(0, 1);
-pub const RIGHT: Direction = Direction(1, 0);
-pub const LEFT: Direction = Direction(-1, 0);
-
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub struct Point(isize, isize);
 
-#[derive(Eq, PartialEq)]
-/// Construction intentionally left private, we only expose a set directions (each 8th)
-pub struct Direction(isize, isize);
-
-impl Direction {
-    pub fn rotate90(&'static self) -> &'static Direction {
-        match self {
-            &DOWN_LEFT => &UP_LEFT,
-            &DOWN => &LEFT,
-            &DOWN_RIGHT => &DOWN_LEFT,
-            &RIGHT => &DOWN,
-            &UP_RIGHT => &DOWN_RIGHT,
-            &UP => &RIGHT,
-            &UP_LEFT => &UP_RIGHT,
-            &LEFT => &UP,
-            _ => panic!("Impossible")
-        }
+// The four orthogonal headings, declared clockwise so `as usize` doubles as an index.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Dir4 {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+// All eight headings, declared clockwise.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Dir8 {
+    Up,
+    UpRight,
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
+}
+
+impl Dir4 {
+    pub const ALL: [Dir4; 4] = [Dir4::Up, Dir4::Right, Dir4::Down, Dir4::Left];
+
+    // Clockwise
+    pub fn rotate90(self) -> Dir4 {
+        return Dir4::ALL[(self as usize + 1) % 4];
+    }
+
+    pub fn rotate_ccw90(self) -> Dir4 {
+        return Dir4::ALL[(self as usize + 3) % 4];
+    }
+
+    pub fn opposite(self) -> Dir4 {
+        return Dir4::ALL[(self as usize + 2) % 4];
+    }
+
+    pub fn glyph(self) -> char {
+        return match self {
+            Dir4::Up => '^',
+            Dir4::Right => '>',
+            Dir4::Down => 'v',
+            Dir4::Left => '<',
+        };
+    }
+}
+
+impl TryFrom<char> for Dir4 {
+    type Error = &'static str;
+
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        return Ok(match c {
+            '^' => Dir4::Up,
+            '>' => Dir4::Right,
+            'v' => Dir4::Down,
+            '<' => Dir4::Left,
+            _ => return Err("Not an arrow"),
+        });
+    }
+}
+
+impl FromStr for Dir4 {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) => c.try_into(),
+            _ => Err("Too long or too short!"),
+        };
+    }
+}
+
+impl TryFrom<Dir8> for Dir4 {
+    type Error = &'static str;
+
+    fn try_from(d: Dir8) -> Result<Self, Self::Error> {
+        return Ok(match d {
+            Dir8::Up => Dir4::Up,
+            Dir8::Right => Dir4::Right,
+            Dir8::Down => Dir4::Down,
+            Dir8::Left => Dir4::Left,
+            Dir8::UpRight | Dir8::DownRight | Dir8::DownLeft | Dir8::UpLeft => {
+                return Err("Diagonal");
+            },
+        });
+    }
+}
+
+impl Dir8 {
+    pub const ALL: [Dir8; 8] = [
+        Dir8::Up,
+        Dir8::UpRight,
+        Dir8::Right,
+        Dir8::DownRight,
+        Dir8::Down,
+        Dir8::DownLeft,
+        Dir8::Left,
+        Dir8::UpLeft,
+    ];
+
+    // Clockwise
+    pub fn rotate45(self) -> Dir8 {
+        return Dir8::ALL[(self as usize + 1) % 8];
+    }
+
+    // Clockwise
+    pub fn rotate90(self) -> Dir8 {
+        return Dir8::ALL[(self as usize + 2) % 8];
+    }
+
+    pub fn opposite(self) -> Dir8 {
+        return Dir8::ALL[(self as usize + 4) % 8];
+    }
+
+    pub fn is_diagonal(self) -> bool {
+        return Dir4::try_from(self).is_err();
+    }
+
+    // (dx, dy) with y growing downwards.
+    pub fn delta(self) -> (isize, isize) {
+        return match self {
+            Dir8::Up => (0, -1),
+            Dir8::UpRight => (1, -1),
+            Dir8::Right => (1, 0),
+            Dir8::DownRight => (1, 1),
+            Dir8::Down => (0, 1),
+            Dir8::DownLeft => (-1, 1),
+            Dir8::Left => (-1, 0),
+            Dir8::UpLeft => (-1, -1),
+        };
+    }
+}
+
+impl From<Dir4> for Dir8 {
+    fn from(d: Dir4) -> Self {
+        return match d {
+            Dir4::Up => Dir8::Up,
+            Dir4::Right => Dir8::Right,
+            Dir4::Down => Dir8::Down,
+            Dir4::Left => Dir8::Left,
+        };
     }
 }
 
 impl Point {
-    pub fn add(&self, d: &Direction) -> Point {
-        return Point(self.0 + d.0, self.1 + d.1);
+    pub fn new(x: isize, y: isize) -> Point {
+        return Point(x, y);
+    }
+
+    pub fn x(&self) -> isize {
+        return self.0;
+    }
+
+    pub fn y(&self) -> isize {
+        return self.1;
+    }
+
+    pub fn add(&self, d: impl Into<Dir8>) -> Point {
+        let (dx, dy) = d.into().delta();
+        return Point(self.0 + dx, self.1 + dy);
     }
 }
 
+// What `at` (and everything built on it) sees outside the parsed cells.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Edge<T> {
+    // Nothing, `at` returns `None`.
+    Bounded,
+    // Coordinates wrap around like a torus.
+    Wrap,
+    // Coordinates stick to the nearest edge cell.
+    Clamp,
+    // Every outside cell holds this value.
+    Border(T),
+}
+
 pub struct Grid<T> {
     data: Vec<Vec<T>>,
+    edge: Edge<T>,
 }
 
 pub struct GridSearch<'a, T, P> {
@@ -59,7 +203,7 @@ where
         let mut last_x = self.x;
         for sy in self.y..self.g.data.len() {
             for sx in last_x..self.g.data[sy].len() {
-                if (self.pred)(&self.g, &Point(sx as isize, sy as isize)) {
+                if (self.pred)(self.g, &Point(sx as isize, sy as isize)) {
                     if sx + 1 == self.g.data[sy].len() {
                         self.x = 0;
                         self.y = sy + 1;
@@ -94,7 +238,7 @@ where
         };
     }
 
-    pub fn match_direction(&self, start: &Point, dir: &Direction, to_match: &[T]) -> bool {
+    pub fn match_direction(&self, start: &Point, dir: Dir8, to_match: &[T]) -> bool {
         let mut nxt_point = start.clone();
         for nxt_char in to_match.iter() {
             match self.at(&nxt_point) {
@@ -111,21 +255,104 @@ where
     }
 }
 
+fn fit(edge: &Edge<impl Sized>, n: isize, len: usize) -> Option<isize> {
+    let len = len as isize;
+    if len == 0 {
+        return None;
+    }
+
+    let n = match edge {
+        Edge::Wrap => n.rem_euclid(len),
+        Edge::Clamp => n.clamp(0, len - 1),
+        Edge::Bounded | Edge::Border(_) => n,
+    };
+    return if n < 0 || n >= len { None } else { Some(n) };
+}
+
 impl<T> Grid<T> {
+    pub fn with_edge(self, edge: Edge<T>) -> Grid<T> {
+        return Grid { edge, ..self };
+    }
+
+    pub fn edge(&self) -> &Edge<T> {
+        return &self.edge;
+    }
+
+    // The parsed cell `p` addresses under the edge mode, if any.
+    pub fn resolve(&self, p: &Point) -> Option<Point> {
+        let y = fit(&self.edge, p.1, self.data.len())?;
+        let x = fit(&self.edge, p.0, self.data[y as usize].len())?;
+        return Some(Point(x, y));
+    }
+
     pub fn at<'a>(&'a self, p: &Point) -> Option<&'a T> {
-        if p.1 < 0 || p.1 as usize >= self.data.len() {
-            return None;
-        }
+        return match (self.resolve(p), &self.edge) {
+            (Some(Point(x, y)), _) => Some(&self.data[y as usize][x as usize]),
+            (None, Edge::Border(b)) => Some(b),
+            (None, _) => None,
+        };
+    }
 
-        if p.0 < 0 || p.0 as usize >= self.data[p.1 as usize].len() {
+    // Like `at`, but only ever the parsed cells.
+    pub fn get<'a>(&'a self, p: &Point) -> Option<&'a T> {
+        if !self.in_bounds(p) {
             return None;
         }
 
         return Some(&self.data[p.1 as usize][p.0 as usize]);
     }
 
+    // Whether `p` is one of the parsed cells, regardless of edge mode.
     pub fn in_bounds(&self, p: &Point) -> bool {
-        return self.at(p).is_some();
+        return p.0 >= 0
+            && p.1 >= 0
+            && self
+                .data
+                .get(p.1 as usize)
+                .is_some_and(|row| (p.0 as usize) < row.len());
+    }
+
+    // Neighbours under the edge mode. Wrapped or clamped neighbours are
+    // reported at their resolved position, border ones where they were asked for.
+    // Each cell comes up once at most, and never `p` itself, even when a small
+    // grid wraps or clamps several directions onto the same cell.
+    pub fn neighbours<'a, D>(
+        &'a self,
+        p: &Point,
+        dirs: impl IntoIterator<Item = D> + 'a,
+    ) -> impl Iterator<Item = (Point, &'a T)> + 'a
+    where
+        D: Into<Dir8>,
+    {
+        let p = p.clone();
+        let mut seen = vec![self.resolve(&p).unwrap_or(p.clone())];
+        return dirs.into_iter().filter_map(move |d| {
+            let nxt = p.add(d);
+            let v = self.at(&nxt)?;
+            let nxt = self.resolve(&nxt).unwrap_or(nxt);
+            if seen.contains(&nxt) {
+                return None;
+            }
+            seen.push(nxt.clone());
+            return Some((nxt, v));
+        });
+    }
+
+    pub fn neighbours4<'a>(&'a self, p: &Point) -> impl Iterator<Item = (Point, &'a T)> + 'a {
+        return self.neighbours(p, Dir4::ALL);
+    }
+
+    pub fn neighbours8<'a>(&'a self, p: &Point) -> impl Iterator<Item = (Point, &'a T)> + 'a {
+        return self.neighbours(p, Dir8::ALL);
+    }
+
+    // Rows may be ragged, so this is the widest one.
+    pub fn width(&self) -> usize {
+        return self.data.iter().map(Vec::len).max().unwrap_or(0);
+    }
+
+    pub fn height(&self) -> usize {
+        return self.data.len();
     }
 }
 
@@ -145,6 +372,130 @@ where
             }
             rows.push(cols);
         }
-        return Ok(Grid { data: rows });
+        return Ok(Grid {
+            data: rows,
+            edge: Edge::Bounded,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::grid::Dir4;
+    use crate::grid::Dir8;
+    use crate::grid::Edge;
+    use crate::grid::Grid;
+    use crate::grid::Point;
+
+    fn abc() -> Grid<char> {
+        return "abc\ndef\nghi".parse().unwrap();
+    }
+
+    #[test]
+    fn dir4_turns() {
+        for d in Dir4::ALL {
+            assert_eq!(d.rotate90().rotate90(), d.opposite());
+            assert_eq!(d.rotate90().rotate_ccw90(), d);
+            assert_eq!(Dir8::from(d).rotate90(), Dir8::from(d.rotate90()));
+        }
+        assert_eq!(Dir4::Left.rotate90(), Dir4::Up);
+    }
+
+    #[test]
+    fn dir8_turns() {
+        for d in Dir8::ALL {
+            assert_eq!(d.rotate45().rotate45(), d.rotate90());
+            assert_eq!(d.opposite().opposite(), d);
+            let (dx, dy) = d.delta();
+            assert_eq!(d.opposite().delta(), (-dx, -dy));
+        }
+    }
+
+    #[test]
+    fn conversions() {
+        for d in Dir4::ALL {
+            assert_eq!(Dir4::try_from(Dir8::from(d)), Ok(d));
+        }
+        assert!(Dir4::try_from(Dir8::UpLeft).is_err());
+        assert!(Dir8::DownRight.is_diagonal());
+        assert!(!Dir8::Down.is_diagonal());
+    }
+
+    #[test]
+    fn parse_arrows() {
+        let parsed: Vec<Dir4> = "^>v<".chars().map(|c| c.try_into().unwrap()).collect();
+        assert_eq!(parsed, Dir4::ALL);
+        assert!(parsed.iter().all(|d| Dir4::try_from(d.glyph()) == Ok(*d)));
+        assert_eq!("v".parse(), Ok(Dir4::Down));
+        assert!("V".parse::<Dir4>().is_err());
+        assert!("^^".parse::<Dir4>().is_err());
+    }
+
+    #[test]
+    fn bounded_edge() {
+        let g = abc();
+        assert_eq!(g.at(&Point::new(2, 2)), Some(&'i'));
+        assert_eq!(g.at(&Point::new(3, 0)), None);
+        assert_eq!(g.at(&Point::new(0, -1)), None);
+        assert_eq!(g.neighbours4(&Point::new(0, 0)).count(), 2);
+    }
+
+    #[test]
+    fn wrap_edge() {
+        let g = abc().with_edge(Edge::Wrap);
+        assert_eq!(g.at(&Point::new(3, 0)), Some(&'a'));
+        assert_eq!(g.at(&Point::new(-1, -1)), Some(&'i'));
+        assert_eq!(g.at(&Point::new(-7, 5)), Some(&'i'));
+        assert!(!g.in_bounds(&Point::new(3, 0)));
+        assert!(g.match_direction(&Point::new(2, 1), Dir8::Right, &['f', 'd', 'e', 'f']));
+        let up = g
+            .neighbours(&Point::new(1, 0), [Dir4::Up])
+            .collect::<Vec<_>>();
+        assert_eq!(up, vec![(Point::new(1, 2), &'h')]);
+        assert_eq!(g.neighbours8(&Point::new(0, 0)).count(), 8);
+    }
+
+    #[test]
+    fn clamp_edge() {
+        let g = abc().with_edge(Edge::Clamp);
+        assert_eq!(g.at(&Point::new(10, -4)), Some(&'c'));
+        assert_eq!(g.at(&Point::new(-1, 1)), Some(&'d'));
+        assert!(g.match_direction(&Point::new(2, 2), Dir8::Right, &['i', 'i', 'i']));
+        let corner: Vec<Point> = g.neighbours4(&Point::new(0, 0)).map(|(p, _)| p).collect();
+        assert_eq!(corner, vec![Point::new(1, 0), Point::new(0, 1)]);
+        assert_eq!(g.neighbours8(&Point::new(0, 0)).count(), 3);
+        assert_eq!(g.neighbours8(&Point::new(1, 1)).count(), 8);
+    }
+
+    #[test]
+    fn small_grids_list_each_neighbour_once() {
+        let g: Grid<char> = "ab".parse().unwrap();
+        let wrapped = g.with_edge(Edge::Wrap);
+        let found: Vec<(Point, &char)> = wrapped.neighbours8(&Point::new(0, 0)).collect();
+        assert_eq!(found, vec![(Point::new(1, 0), &'b')]);
+        // Asking from outside still leaves out the cell it lands on.
+        assert_eq!(wrapped.neighbours4(&Point::new(2, 0)).count(), 1);
+
+        let g: Grid<char> = "ab".parse().unwrap();
+        let clamped = g.with_edge(Edge::Clamp);
+        assert_eq!(
+            clamped.neighbours8(&Point::new(1, 0)).collect::<Vec<_>>(),
+            vec![(Point::new(0, 0), &'a')]
+        );
+    }
+
+    #[test]
+    fn border_edge() {
+        let g = abc().with_edge(Edge::Border('#'));
+        assert_eq!(g.at(&Point::new(3, 0)), Some(&'#'));
+        assert_eq!(g.at(&Point::new(1, 1)), Some(&'e'));
+        assert!(g.match_direction(&Point::new(1, 1), Dir8::Up, &['e', 'b', '#', '#']));
+        let borders = g
+            .neighbours8(&Point::new(0, 0))
+            .filter(|(_, c)| **c == '#')
+            .map(|(p, _)| p)
+            .collect::<Vec<_>>();
+        assert_eq!(borders.len(), 5);
+        assert!(borders.contains(&Point::new(-1, -1)));
     }
 }
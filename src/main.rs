@@ -1,6 +1,28 @@
+// Explicit returns and `&'static` consts are the house style.
+#![allow(clippy::needless_return)]
+#![allow(clippy::redundant_static_lifetimes)]
 
+#[allow(dead_code)]
+mod answer;
+#[allow(dead_code)]
+mod bitset;
+#[allow(dead_code)]
+mod counter;
+#[allow(dead_code)]
+mod cycle;
+#[allow(dead_code)]
+mod diff;
+#[allow(dead_code)]
+mod digraph;
 // Used this in a few different solutions.
+#[allow(dead_code)]
 mod grid;
+#[allow(dead_code)]
+mod parse;
+#[allow(dead_code)]
+mod rng;
+#[allow(dead_code)]
+mod tally;
 
 mod day1;
 mod day2;
@@ -9,22 +31,92 @@ mod day4;
 mod day5;
 mod day6;
 
+use crate::answer::Answer;
+
 fn main() {
-    day1::run_p1();
-    day1::run_p2();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] => run_all(),
+        ["check", path] => run_check(path),
+        ["day2", "audit"] => day2::run_audit(),
+        ["day2", "whatif", min, max, dirs, k] => day2::run_whatif(
+            min.parse().expect("A min step"),
+            max.parse().expect("A max step"),
+            dirs,
+            k.parse().expect("A number of removals"),
+        ),
+        ["day3", "annotate"] => day3::run_annotate(),
+        ["day3", "bench"] => day3::run_bench(day3::BenchInput::Generate(8)),
+        ["day3", "bench", arg] => day3::run_bench(match arg.parse() {
+            Ok(megabytes) => day3::BenchInput::Generate(megabytes),
+            Err(_) => day3::BenchInput::File(arg),
+        }),
+        ["day3", "stream"] => day3::run_stream(None),
+        ["day3", "stream", path] => day3::run_stream(Some(path)),
+        ["day5", "explain"] => day5::run_explain(),
+        ["day5", "orderings"] => day5::run_orderings(),
+        ["day5", "dot"] => day5::run_dot(None),
+        ["day5", "dot", n] => day5::run_dot(Some(n.parse().expect("An update number"))),
+        _ => {
+            eprintln!(
+                "Usage: aoc-2024 [check <answers file> | day2 audit \
+                 | day2 whatif <min> <max> <asc|desc|either> <removals> | day3 annotate \
+                 | day3 bench [MiB | file] | day3 stream [file] \
+                 | day5 explain | day5 orderings | day5 dot [update]]"
+            );
+            std::process::exit(1);
+        },
+    }
+}
 
-    day2::run_p1();
-    day2::run_p2();
+type Solver = fn() -> Answer;
 
-    day3::run_p1();
-    day3::run_p2();
+// Every part, in the order they're run.
+const SOLVERS: &'static [(u8, u8, Solver)] = &[
+    (1, 1, day1::run_p1),
+    (1, 2, day1::run_p2),
+    (2, 1, day2::run_p1),
+    (2, 2, day2::run_p2),
+    (3, 1, day3::run_p1),
+    (3, 2, day3::run_p2),
+    (4, 1, day4::run_p1),
+    (4, 2, day4::run_p2),
+    (5, 1, day5::run_p1),
+    (5, 2, day5::run_p2),
+    (6, 1, day6::run_p1),
+    (6, 2, day6::run_p2),
+];
+
+fn run_all() {
+    for (day, part, solve) in SOLVERS {
+        println!("Day {}, Part {}: {}", day, part, solve());
+    }
+}
 
-    day4::run_p1();
-    day4::run_p2();
+// Runs every part against a file of expected answers, failing on any mismatch.
+fn run_check(path: &str) {
+    let contents = std::fs::read_to_string(path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    let expected = answer::parse_expected(&contents).unwrap_or_else(|e| panic!("{}, {}", path, e));
 
-    day5::run_p1();
-    day5::run_p2();
+    let mut wrong = 0;
+    for (day, part, solve) in SOLVERS {
+        let got = solve();
+        match expected.get(&(*day, *part)) {
+            Some(want) if *want == got => println!("Day {}, Part {}: ok", day, part),
+            Some(want) => {
+                println!("Day {}, Part {}: got {}, expected {}", day, part, got, want);
+                wrong += 1;
+            },
+            None => println!("Day {}, Part {}: {} (nothing expected)", day, part, got),
+        }
+    }
 
-    day6::run_p1();
-    day6::run_p2();
+    if wrong > 0 {
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,159 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+// Where the input went wrong, both 1-based. Anything parsing a single line
+// reports line 1 and lets the caller fix it up with `on_line`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub col: usize,
+    pub reason: &'static str,
+}
+
+impl ParseError {
+    pub fn at_col(col: usize, reason: &'static str) -> ParseError {
+        return ParseError {
+            line: 1,
+            col,
+            reason,
+        };
+    }
+
+    pub fn on_line(self, line: usize) -> ParseError {
+        return ParseError { line, ..self };
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.col, self.reason
+        );
+    }
+}
+
+// Whitespace-separated tokens with their 1-based starting column.
+pub fn tokens(s: &str) -> impl Iterator<Item = (usize, &str)> {
+    // Each token borrows from `s`, so the distance between them is the offset.
+    return s
+        .split_whitespace()
+        .map(move |tok| (tok.as_ptr() as usize - s.as_ptr() as usize + 1, tok));
+}
+
+// One line of whitespace-separated numbers, at least one of them.
+pub fn row<T: FromStr>(line: &str) -> Result<Vec<T>, ParseError> {
+    let mut out = Vec::new();
+    for (col, tok) in tokens(line) {
+        out.push(
+            tok.parse()
+                .map_err(|_| ParseError::at_col(col, "Not a number"))?,
+        );
+    }
+
+    if out.is_empty() {
+        return Err(ParseError::at_col(1, "Empty row"));
+    }
+
+    return Ok(out);
+}
+
+// A row per line, any length each.
+pub fn rows<T: FromStr>(s: &str) -> Result<Vec<Vec<T>>, ParseError> {
+    return s
+        .lines()
+        .enumerate()
+        .map(|(idx, l)| row(l).map_err(|e| e.on_line(idx + 1)))
+        .collect();
+}
+
+// Exactly `N` numbers per line, handed back a column at a time.
+pub fn columns<T: FromStr, const N: usize>(s: &str) -> Result<[Vec<T>; N], ParseError> {
+    let mut out: [Vec<T>; N] = std::array::from_fn(|_| Vec::new());
+    for (idx, l) in s.lines().enumerate() {
+        let cells: Vec<T> = row(l).map_err(|e| e.on_line(idx + 1))?;
+        if cells.len() != N {
+            let col = match tokens(l).nth(N) {
+                Some((col, _)) => col,
+                None => l.len() + 1,
+            };
+            let reason = if cells.len() < N {
+                "Too few columns"
+            } else {
+                "Too many columns"
+            };
+            return Err(ParseError::at_col(col, reason).on_line(idx + 1));
+        }
+
+        for (column, cell) in out.iter_mut().zip(cells) {
+            column.push(cell);
+        }
+    }
+
+    return Ok(out);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse::columns;
+    use crate::parse::row;
+    use crate::parse::rows;
+    use crate::parse::tokens;
+    use crate::parse::ParseError;
+
+    #[test]
+    fn token_columns() {
+        let found: Vec<(usize, &str)> = tokens("  12 3\t45").collect();
+        assert_eq!(found, vec![(3, "12"), (6, "3"), (8, "45")]);
+    }
+
+    #[test]
+    fn rows_of_any_length() {
+        assert_eq!(
+            rows::<i32>("1 2 3\n-4\n5 6"),
+            Ok(vec![vec![1, 2, 3], vec![-4], vec![5, 6]])
+        );
+        assert_eq!(
+            rows::<i32>("1 2\n\n3").unwrap_err(),
+            ParseError {
+                line: 2,
+                col: 1,
+                reason: "Empty row",
+            }
+        );
+        assert_eq!(
+            row::<u32>("7 6 x 2").unwrap_err(),
+            ParseError::at_col(5, "Not a number")
+        );
+    }
+
+    #[test]
+    fn fixed_columns() {
+        let [left, right] = columns::<u32, 2>("3   4\n4   3\n2   5").unwrap();
+        assert_eq!(left, vec![3, 4, 2]);
+        assert_eq!(right, vec![4, 3, 5]);
+        let [a, b, c] = columns::<i64, 3>("1 2 3").unwrap();
+        assert_eq!((a, b, c), (vec![1], vec![2], vec![3]));
+    }
+
+    #[test]
+    fn ragged_columns() {
+        assert_eq!(
+            columns::<u32, 2>("3   4\n4\n2   5").unwrap_err(),
+            ParseError {
+                line: 2,
+                col: 2,
+                reason: "Too few columns",
+            }
+        );
+        assert_eq!(
+            columns::<u32, 2>("3 4 5").unwrap_err(),
+            ParseError::at_col(5, "Too many columns")
+        );
+        assert_eq!(
+            columns::<u32, 2>("3 -4").unwrap_err(),
+            ParseError::at_col(3, "Not a number")
+        );
+    }
+}
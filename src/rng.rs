@@ -0,0 +1,31 @@
+// A seeded xorshift, handing back numbers below whatever bound it's asked
+// for. Not random in any sense that matters, but the same seed always gives
+// the same sequence, which is the point.
+pub fn xorshift(seed: u32) -> impl FnMut(u32) -> u32 {
+    // Zero would stay zero forever.
+    let mut state = seed.max(1);
+    return move |bound: u32| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        return state % bound;
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use crate::rng::xorshift;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = xorshift(7);
+        let mut b = xorshift(7);
+        for _ in 0..100 {
+            assert_eq!(a(1000), b(1000));
+        }
+
+        let mut zero = xorshift(0);
+        assert!((0..100).any(|_| zero(1000) != 0));
+        assert!((0..100).all(|_| zero(10) < 10));
+    }
+}
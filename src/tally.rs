@@ -0,0 +1,97 @@
+use std::fmt::Display;
+
+// Wide enough that any of the inputs would need to be absurd to overflow it,
+// but every step is still checked.
+pub type Wide = u128;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Overflow {
+    pub day: u8,
+    pub part: u8,
+}
+
+impl Display for Overflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "Day {}, Part {}: answer overflowed", self.day, self.part);
+    }
+}
+
+// A running answer that knows which puzzle it belongs to, so an overflow
+// can say where it happened.
+#[derive(Debug)]
+pub struct Tally {
+    day: u8,
+    part: u8,
+    total: Wide,
+}
+
+impl Tally {
+    pub fn new(day: u8, part: u8) -> Tally {
+        return Tally {
+            day,
+            part,
+            total: 0,
+        };
+    }
+
+    pub fn overflow(&self) -> Overflow {
+        return Overflow {
+            day: self.day,
+            part: self.part,
+        };
+    }
+
+    pub fn add(&mut self, n: impl Into<Wide>) -> Result<(), Overflow> {
+        self.total = self
+            .total
+            .checked_add(n.into())
+            .ok_or_else(|| self.overflow())?;
+        return Ok(());
+    }
+
+    pub fn add_product(&mut self, a: impl Into<Wide>, b: impl Into<Wide>) -> Result<(), Overflow> {
+        let product = a
+            .into()
+            .checked_mul(b.into())
+            .ok_or_else(|| self.overflow())?;
+        return self.add(product);
+    }
+
+    pub fn total(&self) -> Wide {
+        return self.total;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tally::Overflow;
+    use crate::tally::Tally;
+    use crate::tally::Wide;
+
+    #[test]
+    fn past_u32() {
+        let mut t = Tally::new(1, 1);
+        for _ in 0..3 {
+            t.add(u32::MAX).unwrap();
+        }
+        t.add_product(u32::MAX, u32::MAX).unwrap();
+        let expected = 3 * u32::MAX as Wide + u32::MAX as Wide * u32::MAX as Wide;
+        assert_eq!(t.total(), expected);
+    }
+
+    #[test]
+    fn overflow_names_the_puzzle() {
+        let mut t = Tally::new(3, 2);
+        t.add(Wide::MAX).unwrap();
+        assert_eq!(t.add(1u8), Err(Overflow { day: 3, part: 2 }));
+        // A failed step leaves the total alone.
+        assert_eq!(t.total(), Wide::MAX);
+
+        let mut t = Tally::new(5, 1);
+        assert_eq!(
+            t.add_product(Wide::MAX, 2u8).unwrap_err().to_string(),
+            "Day 5, Part 1: answer overflowed"
+        );
+        assert_eq!(t.total(), 0);
+    }
+}